@@ -0,0 +1,128 @@
+//! Splitting of the query input into words and operators.
+//!
+//! The recursive-descent parser asks for the token at the beginning of the rest of the input, so
+//! that the operators can be spelled in several ways without the parser knowing about them.
+
+/// The boolean operators, each of which has several spellings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum Operator {
+    /// `AND` in any case, or `&&`.
+    And,
+    /// `OR` in any case, or `||`.
+    Or,
+    /// `NOT`, or `-` before a term.
+    ///
+    /// Lowercase `not` is left as a word since it is common in titles like "why not rust".
+    Not,
+    /// `+` before a term, which is required even when the default operator is `OR`.
+    Required,
+}
+
+/// Splits the operator at the beginning of `input`, if any.
+///
+/// The spelled-out operators like `AND` must be whole words, so that words like `ORACLE` are not
+/// split into an operator and the rest.
+pub(crate) fn split_operator(input: &str) -> Option<(Operator, &str)> {
+    if let Some(rest) = input.strip_prefix('-') {
+        return Some((Operator::Not, rest));
+    }
+    if let Some(rest) = input.strip_prefix('+') {
+        return Some((Operator::Required, rest));
+    }
+
+    let (word, rest) = split_word(input);
+    if word == "&&" || word.eq_ignore_ascii_case("AND") {
+        Some((Operator::And, rest))
+    } else if word == "||" || word.eq_ignore_ascii_case("OR") {
+        Some((Operator::Or, rest))
+    } else if word == "NOT" {
+        Some((Operator::Not, rest))
+    } else {
+        None
+    }
+}
+
+/// Splits `input` at the first whitespace, parenthesis, `&&` or `||` that is not escaped by a
+/// backslash.
+///
+/// `&&` and `||` are words by themselves even if they are not surrounded by whitespaces.
+pub(crate) fn split_word(input: &str) -> (&str, &str) {
+    let is_symbol = |input: &str| input.starts_with("&&") || input.starts_with("||");
+    if is_symbol(input) {
+        return input.split_at(2);
+    }
+
+    let mut chars = input.char_indices();
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            c if c.is_whitespace() || c == '(' || c == ')' => return input.split_at(idx),
+            '&' | '|' if is_symbol(&input[idx..]) => return input.split_at(idx),
+            _ => {}
+        }
+    }
+    (input, "")
+}
+
+/// Returns true if the character following `word` is escaped by a backslash at the end of `word`.
+pub(crate) fn ends_with_escape(word: &str) -> bool {
+    word.chars().rev().take_while(|&c| c == '\\').count() % 2 == 1
+}
+
+/// Resolves the backslash escapes in a word split by `split_word`.
+///
+/// Returns `None` if the word ends with a backslash escaping nothing.
+pub(crate) fn unescape(word: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(word.len());
+    let mut chars = word.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.push(chars.next()?),
+            c => unescaped.push(c),
+        }
+    }
+    Some(unescaped)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_split_operator() {
+        for (input, operator) in [
+            ("AND x", Operator::And),
+            ("and x", Operator::And),
+            ("And x", Operator::And),
+            ("&& x", Operator::And),
+            ("OR x", Operator::Or),
+            ("or x", Operator::Or),
+            ("oR x", Operator::Or),
+            ("|| x", Operator::Or),
+            ("NOT x", Operator::Not),
+            ("- x", Operator::Not),
+            ("+ x", Operator::Required),
+        ] {
+            assert_eq!(split_operator(input), Some((operator, " x")), "{input}");
+        }
+
+        assert_eq!(split_operator("&&x"), Some((Operator::And, "x")));
+        assert_eq!(split_operator("OR(x)"), Some((Operator::Or, "(x)")));
+        assert_eq!(split_operator("-x"), Some((Operator::Not, "x")));
+        assert_eq!(split_operator("+x"), Some((Operator::Required, "x")));
+        for input in ["ORACLE", "android", "not x", "NOTE", "&x", "|x", "x"] {
+            assert_eq!(split_operator(input), None, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_split_word() {
+        assert_eq!(split_word("rust&&go"), ("rust", "&&go"));
+        assert_eq!(split_word("||go"), ("||", "go"));
+        assert_eq!(split_word("r&d |x"), ("r&d", " |x"));
+        assert_eq!(split_word("a\\&&b c"), ("a\\&&b", " c"));
+        assert_eq!(split_word("\\(x\\) y"), ("\\(x\\)", " y"));
+    }
+}
@@ -1,70 +1,725 @@
+use std::fmt;
+
+use lexer::{ends_with_escape, split_operator, split_word, unescape, Operator};
+
+mod lexer;
+pub mod normalize;
+
+/// What the parser expected where it failed to parse a query.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct ParseError;
+pub enum Expected {
+    /// A term like a word, a phrase, or a parenthesized query.
+    Term,
+    /// A plain word, as in the operands of `NEAR/k`.
+    Word,
+    /// The closing parenthesis of a parenthesized query.
+    CloseParen,
+    /// The closing quotation mark of a phrase.
+    CloseQuote,
+    /// The closing slash of a regular expression.
+    CloseSlash,
+    /// The number of words after `NEAR/`.
+    NearDistance,
+    /// The edit distance after `~`.
+    FuzzyDistance,
+    /// The value of a bound in a range predicate.
+    RangeValue,
+    /// The character escaped by a backslash.
+    EscapedChar,
+    /// An operator continuing the query, or the end of the query.
+    Operator,
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expected::Term => write!(f, "a term"),
+            Expected::Word => write!(f, "a word"),
+            Expected::CloseParen => write!(f, "`)`"),
+            Expected::CloseQuote => write!(f, "`\"`"),
+            Expected::CloseSlash => write!(f, "`/`"),
+            Expected::NearDistance => write!(f, "a distance like `NEAR/3`"),
+            Expected::FuzzyDistance => {
+                write!(f, "an edit distance up to {MAX_FUZZY_DISTANCE}")
+            }
+            Expected::RangeValue => write!(f, "a value"),
+            Expected::EscapedChar => write!(f, "a character after `\\`"),
+            Expected::Operator => write!(f, "`AND`, `OR` or the end of the query"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ParseError {
+    /// The byte offset in the input where the error was found.
+    pub offset: usize,
+    /// The token found at `offset`, or `None` at the end of the input.
+    pub found: Option<String>,
+    pub expected: Expected,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.found {
+            Some(found) => write!(f, "expected {}, found `{found}`", self.expected),
+            None => write!(f, "expected {}, found the end of the query", self.expected),
+        }
+    }
+}
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+impl std::error::Error for ParseError {}
+
+/// A parse error located by the rest of the input where it was found, which `parse` converts to
+/// `ParseError`.
+#[derive(Debug)]
+struct Error<'a> {
+    rest: &'a str,
+    expected: Expected,
+}
+
+fn error(rest: &str, expected: Expected) -> Error<'_> {
+    Error { rest, expected }
+}
+
+/// A bound of the range in a range predicate like `score:>=100`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RangeBound {
+    Included(String),
+    Excluded(String),
+    Unbounded,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Query {
     Word(String),
+    /// A word matched as written without stemming, like `=running`.
+    Exact(String),
+    /// Words that must appear adjacent to each other in this order.
+    ///
+    /// `None` is a gap matching any word, written as `?` like `"state ? ? art"`, which an analyzer
+    /// leaves in place of the words it removes, like stopwords.
+    Phrase(Vec<Option<String>>),
+    /// Two words that appear within the given number of words of each other, in either order.
+    Near(String, String, u32),
+    /// Any word starting with the prefix.
+    Prefix(String),
+    /// Any word within the given Levenshtein distance from the word.
+    Fuzzy(String, u32),
+    /// Any word matching the regular expression in its entirety.
+    Regex(String),
+    /// The query searched in the named field instead of the default one, like `by:pg`.
+    Field(String, Box<Query>),
+    /// The documents whose named field is within the lower and upper bounds, like `score:>=100`.
+    Range(String, RangeBound, RangeBound),
     And(Box<Query>, Box<Query>),
     Or(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+}
+
+/// Returns true if `word` is parsed as an operator rather than a word.
+fn is_keyword(word: &str) -> bool {
+    matches!(split_operator(word), Some((_, ""))) || word.starts_with("NEAR/")
+}
+
+/// Escapes the backslashes, the quotation marks, and the whitespaces in a word inside quotes.
+fn escape_quoted(word: &str) -> String {
+    let mut escaped = String::with_capacity(word.len());
+    for c in word.chars() {
+        if c == '\\' || c == '"' || c.is_whitespace() {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Escapes the characters that have special meanings in an unquoted term like `c\+\+*`.
+fn escape_term(term: &str) -> String {
+    let mut escaped = String::with_capacity(term.len());
+    for (idx, c) in term.char_indices() {
+        if c.is_whitespace() || "()\\\"*~:/&|".contains(c) || (idx == 0 && "-+=".contains(c)) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Writes a word so that it parses back to `Query::Word(word)` wherever it appears.
+fn write_word(f: &mut fmt::Formatter<'_>, word: &str) -> fmt::Result {
+    let is_plain = !is_keyword(word)
+        && matches!(
+            primary_expr(word, ParseOptions::default()),
+            Ok(("", Query::Word(parsed))) if parsed == word
+        );
+    if is_plain {
+        write!(f, "{word}")
+    } else {
+        write!(f, "\"{}\"", escape_quoted(word))
+    }
+}
+
+/// Writes `query` as an operand, parenthesizing it if it binds looser than `precedence`.
+fn write_operand(f: &mut fmt::Formatter<'_>, query: &Query, precedence: u8) -> fmt::Result {
+    if query.precedence() < precedence {
+        write!(f, "({query})")
+    } else {
+        write!(f, "{query}")
+    }
+}
+
+/// Writes the right operand of `AND` or `OR`, which continues the chain without parentheses if it
+/// has the same operator.
+///
+/// Whether `AND` or `OR` binds tighter depends on `Precedence`, so an operand with the other
+/// operator is always parenthesized.
+fn write_chain(f: &mut fmt::Formatter<'_>, query: &Query, is_same_operator: bool) -> fmt::Result {
+    if is_same_operator {
+        write!(f, "{query}")
+    } else {
+        write_operand(f, query, 2)
+    }
+}
+
+impl Query {
+    /// How tightly the query binds when it is written as an operand of another query.
+    fn precedence(&self) -> u8 {
+        match self {
+            Query::And(_, _) => 0,
+            Query::Or(_, _) => 1,
+            Query::Near(_, _, _) => 2,
+            Query::Not(_) => 3,
+            Query::Field(_, _) | Query::Range(_, _, _) => 4,
+            Query::Word(_)
+            | Query::Exact(_)
+            | Query::Phrase(_)
+            | Query::Prefix(_)
+            | Query::Fuzzy(_, _)
+            | Query::Regex(_) => 5,
+        }
+    }
+}
+
+/// Writes the query in the canonical syntax with as few parentheses as possible.
+///
+/// The operands of `AND` and `OR` mixing the two operators are parenthesized, so that the canonical
+/// form means the same under either `Precedence`. Every query returned by `parse_with_options`
+/// parses back to the same query from its canonical form with any options. Queries that `parse` never returns, like a range with two exclusive bounds, are
+/// written in an equivalent but not necessarily identical form.
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Query::Word(word) => write_word(f, word),
+            Query::Exact(word) => {
+                write!(f, "=")?;
+                write_word(f, word)
+            }
+            Query::Phrase(words) => {
+                let words: Vec<_> = words
+                    .iter()
+                    .map(|word| match word {
+                        Some(word) if word == "?" => "\\?".to_string(),
+                        Some(word) => escape_quoted(word),
+                        None => "?".to_string(),
+                    })
+                    .collect();
+                write!(f, "\"{}\"", words.join(" "))
+            }
+            Query::Near(lhs, rhs, distance) => {
+                write_word(f, lhs)?;
+                write!(f, " NEAR/{distance} ")?;
+                write_word(f, rhs)
+            }
+            Query::Prefix(prefix) => write!(f, "{}*", escape_term(prefix)),
+            Query::Fuzzy(word, distance) => write!(f, "{}~{distance}", escape_term(word)),
+            Query::Regex(pattern) => write!(f, "/{}/", pattern.replace('/', "\\/")),
+            Query::Field(field, query) => {
+                let query = match query.as_ref() {
+                    Query::Field(_, _) | Query::Range(_, _, _) => format!("({query})"),
+                    query if query.precedence() < 3 => format!("({query})"),
+                    query => query.to_string(),
+                };
+                // Parenthesize the query if it looks like a range after the field name.
+                if matches!(range_expr(&query), Ok(None)) {
+                    write!(f, "{field}:{query}")
+                } else {
+                    write!(f, "{field}:({query})")
+                }
+            }
+            Query::Range(field, lower, upper) => match (lower, upper) {
+                (RangeBound::Included(lower), RangeBound::Unbounded) => {
+                    write!(f, "{field}:>={lower}")
+                }
+                (RangeBound::Excluded(lower), RangeBound::Unbounded) => {
+                    write!(f, "{field}:>{lower}")
+                }
+                (RangeBound::Unbounded, RangeBound::Included(upper)) => {
+                    write!(f, "{field}:<={upper}")
+                }
+                (RangeBound::Unbounded, RangeBound::Excluded(upper)) => {
+                    write!(f, "{field}:<{upper}")
+                }
+                (RangeBound::Included(lower), RangeBound::Included(upper)) => {
+                    write!(f, "{field}:{lower}..{upper}")
+                }
+                (RangeBound::Unbounded, RangeBound::Unbounded) => write!(f, "{field}:.."),
+                (lower, upper) => {
+                    let lower = Query::Range(field.clone(), lower.clone(), RangeBound::Unbounded);
+                    let upper = Query::Range(field.clone(), RangeBound::Unbounded, upper.clone());
+                    write!(f, "({lower} AND {upper})")
+                }
+            },
+            Query::And(lhs, rhs) => {
+                write_operand(f, lhs, 2)?;
+                write!(f, " AND ")?;
+                write_chain(f, rhs, matches!(rhs.as_ref(), Query::And(_, _)))
+            }
+            Query::Or(lhs, rhs) => {
+                write_operand(f, lhs, 2)?;
+                write!(f, " OR ")?;
+                write_chain(f, rhs, matches!(rhs.as_ref(), Query::Or(_, _)))
+            }
+            Query::Not(query) => {
+                write!(f, "-")?;
+                write_operand(f, query, 3)
+            }
+        }
+    }
+}
+
+/// The operator combining the clauses juxtaposed without an operator like `rust async runtime`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum DefaultOperator {
+    /// Requires every clause, like web search engines.
+    #[default]
+    And,
+    /// Requires any of the clauses, except that negated clauses and clauses marked with `+` are
+    /// still required as in Lucene. For example, `rust go +async -java` means
+    /// `(rust OR go) AND async AND NOT java`.
+    Or,
 }
 
-fn primary_expr(input: &str) -> Result<(&str, Query), ParseError> {
+impl fmt::Display for DefaultOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DefaultOperator::And => write!(f, "AND"),
+            DefaultOperator::Or => write!(f, "OR"),
+        }
+    }
+}
+
+impl DefaultOperator {
+    /// Combines the clauses, each of which is paired with whether it is marked with `+`.
+    fn combine(self, clauses: Vec<(Query, bool)>) -> Query {
+        // Combine right-associatively as the explicit operators do.
+        fn fold(clauses: Vec<Query>, op: fn(Box<Query>, Box<Query>) -> Query) -> Option<Query> {
+            clauses
+                .into_iter()
+                .rev()
+                .reduce(|rhs, lhs| op(Box::new(lhs), Box::new(rhs)))
+        }
+
+        match self {
+            DefaultOperator::And => fold(
+                clauses.into_iter().map(|(clause, _)| clause).collect(),
+                Query::And,
+            )
+            .unwrap(),
+            DefaultOperator::Or => {
+                let (required, optional): (Vec<_>, Vec<_>) = clauses
+                    .into_iter()
+                    .partition(|(clause, required)| *required || matches!(clause, Query::Not(_)));
+                let optional = optional.into_iter().map(|(clause, _)| clause).collect();
+                let clauses = fold(optional, Query::Or)
+                    .into_iter()
+                    .chain(required.into_iter().map(|(clause, _)| clause))
+                    .collect();
+                fold(clauses, Query::And).unwrap()
+            }
+        }
+    }
+}
+
+/// The relative precedence of `AND` and `OR`.
+///
+/// In both modes, juxtaposed clauses like `rust async` bind just tighter than `AND`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Precedence {
+    /// `OR` binds tighter than `AND`: `foo AND bar OR baz` means `foo AND (bar OR baz)`.
+    ///
+    /// This is the grammar the parser has always had, kept for compatibility.
+    #[default]
+    Legacy,
+    /// `AND` binds tighter than `OR` as in SQL or Lucene: `foo AND bar OR baz` means
+    /// `(foo AND bar) OR baz`.
+    Standard,
+}
+
+impl fmt::Display for Precedence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Precedence::Legacy => write!(f, "legacy (OR binds tighter than AND)"),
+            Precedence::Standard => write!(f, "standard (AND binds tighter than OR)"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct ParseOptions {
+    pub default_operator: DefaultOperator,
+    pub precedence: Precedence,
+}
+
+impl fmt::Display for ParseOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "default operator {}, {} precedence",
+            self.default_operator, self.precedence
+        )
+    }
+}
+
+/// The maximum edit distance allowed in fuzzy terms like `word~2`.
+pub const MAX_FUZZY_DISTANCE: u32 = 2;
+
+/// The names of the fields that can precede a colon in queries, which are the indexed fields like
+/// `by:pg` and the stored fields supporting ranges like `score:>=100`.
+///
+/// Other words followed by a colon, like `https://github.com` and `re:invent`, are plain words.
+pub const FIELDS: [&str; 10] = [
+    "title",
+    "by",
+    "url",
+    "text",
+    "author",
+    "exact_title",
+    "exact_text",
+    "domain",
+    "score",
+    "date",
+];
+
+/// Splits a field name followed by a colon like `title:` from the beginning of `input`.
+fn split_field(input: &str) -> Option<(&str, &str)> {
+    let (field, input) = input.split_at(input.find(|c: char| !c.is_ascii_lowercase() && c != '_')?);
+    let input = input.strip_prefix(':')?;
+    if !FIELDS.contains(&field)
+        || input.is_empty()
+        || input.starts_with(|c: char| c.is_whitespace() || c == ')')
+    {
+        None
+    } else {
+        Some((field, input))
+    }
+}
+
+/// Parses a range like `>=100` or `2020-01-01..2021-12-31` following a field name.
+///
+/// Returns `None` if `input` does not start with a range, including the words with `..` whose sides
+/// are not numbers or dates.
+fn range_expr(input: &str) -> Result<Option<(&str, RangeBound, RangeBound)>, Error<'_>> {
+    let (value, rest) = split_word(input);
+
+    // `value` is a suffix of the range, so the missing value is at its end.
+    let bound = |value: &str, bound: fn(String) -> RangeBound| {
+        if value.is_empty() {
+            Err(error(rest, Expected::RangeValue))
+        } else {
+            Ok(bound(value.to_string()))
+        }
+    };
+    let (lower, upper) = if let Some(value) = value.strip_prefix(">=") {
+        (bound(value, RangeBound::Included)?, RangeBound::Unbounded)
+    } else if let Some(value) = value.strip_prefix('>') {
+        (bound(value, RangeBound::Excluded)?, RangeBound::Unbounded)
+    } else if let Some(value) = value.strip_prefix("<=") {
+        (RangeBound::Unbounded, bound(value, RangeBound::Included)?)
+    } else if let Some(value) = value.strip_prefix('<') {
+        (RangeBound::Unbounded, bound(value, RangeBound::Excluded)?)
+    } else if let Some((lower, upper)) = value.split_once("..") {
+        if lower.is_empty() && upper.is_empty() {
+            return Err(error(rest, Expected::RangeValue));
+        }
+        // Words like `a..b` and `...` in fields like `url:` are not ranges, so the bounds must be
+        // numbers or dates, which start with digits.
+        let is_bound =
+            |value: &str| value.is_empty() || value.starts_with(|c: char| c.is_ascii_digit());
+        if !is_bound(lower) || !is_bound(upper) {
+            return Ok(None);
+        }
+        let lower = bound(lower, RangeBound::Included).unwrap_or(RangeBound::Unbounded);
+        let upper = bound(upper, RangeBound::Included).unwrap_or(RangeBound::Unbounded);
+        (lower, upper)
+    } else {
+        return Ok(None);
+    };
+
+    Ok(Some((rest, lower, upper)))
+}
+
+fn primary_expr(input: &str, options: ParseOptions) -> Result<(&str, Query), Error<'_>> {
     let input = input.trim_start();
+    if let Some((field, input)) = split_field(input) {
+        if let Some((input, lower, upper)) = range_expr(input)? {
+            return Ok((input, Query::Range(field.to_string(), lower, upper)));
+        }
+        let (input, query) = term_expr(input, options)?;
+        Ok((input, Query::Field(field.to_string(), Box::new(query))))
+    } else {
+        term_expr(input, options)
+    }
+}
+
+fn term_expr(input: &str, options: ParseOptions) -> Result<(&str, Query), Error<'_>> {
     if let Some(input) = input.strip_prefix('(') {
-        let (input, query) = expr(input)?;
+        let (input, query) = expr(input, options)?;
         let input = input.trim_start();
-        if !input.starts_with(')') {
-            return Err(ParseError);
+        match input.strip_prefix(')') {
+            Some(input) => Ok((input, query)),
+            None => Err(error(input, Expected::CloseParen)),
+        }
+    } else if let Some(rest) = input.strip_prefix('"') {
+        // Backslashes escape the quotation marks, the whitespaces and themselves in the phrase.
+        // An unescaped `?` is a gap matching any word.
+        let mut words = vec![];
+        let mut word = String::new();
+        let mut escaped = false;
+        let mut push_word = |word: &mut String, escaped: &mut bool| {
+            if !word.is_empty() {
+                let word = std::mem::take(word);
+                words.push((word != "?" || *escaped).then_some(word));
+            }
+            *escaped = false;
+        };
+        let mut chars = rest.char_indices();
+        let rest = loop {
+            match chars.next() {
+                Some((idx, '"')) => break &rest[idx + 1..],
+                Some((_, '\\')) => match chars.next() {
+                    Some((_, c)) => {
+                        word.push(c);
+                        escaped = true;
+                    }
+                    None => return Err(error(&rest[rest.len()..], Expected::EscapedChar)),
+                },
+                Some((_, c)) if c.is_whitespace() => push_word(&mut word, &mut escaped),
+                Some((_, c)) => word.push(c),
+                None => return Err(error(&rest[rest.len()..], Expected::CloseQuote)),
+            }
+        };
+        push_word(&mut word, &mut escaped);
+
+        // The gaps at the ends match nothing in particular, so they are dropped.
+        let start = words.iter().position(Option::is_some);
+        let end = words.iter().rposition(Option::is_some);
+        let mut words = match (start, end) {
+            (Some(start), Some(end)) => words[start..=end].to_vec(),
+            _ => vec![],
+        };
+        match words.len() {
+            0 => Err(error(input, Expected::Term)),
+            1 => Ok((rest, Query::Word(words.pop().unwrap().unwrap()))),
+            _ => Ok((rest, Query::Phrase(words))),
+        }
+    } else if let Some(rest) = input.strip_prefix('/') {
+        // The pattern ends at the first slash that is not escaped by a backslash.
+        let mut pattern = String::new();
+        let mut chars = rest.char_indices();
+        while let Some((idx, c)) = chars.next() {
+            match c {
+                '/' if pattern.is_empty() => return Err(error(input, Expected::Term)),
+                '/' => return Ok((&rest[idx + 1..], Query::Regex(pattern))),
+                '\\' => match chars.next() {
+                    Some((_, '/')) => pattern.push('/'),
+                    Some((_, c)) => {
+                        pattern.push('\\');
+                        pattern.push(c);
+                    }
+                    None => break,
+                },
+                c => pattern.push(c),
+            }
+        }
+        Err(error(&rest[rest.len()..], Expected::CloseSlash))
+    } else if let Some(rest) = input.strip_prefix('=') {
+        // Only a word, possibly quoted, can be matched exactly.
+        match term_expr(rest, options)? {
+            (rest, Query::Word(word)) => Ok((rest, Query::Exact(word))),
+            _ => Err(error(rest, Expected::Word)),
+        }
+    } else if let Some((operator, rest)) = split_operator(input) {
+        match operator {
+            Operator::Not => {
+                let (rest, query) = primary_expr(rest, options)?;
+                Ok((rest, Query::Not(Box::new(query))))
+            }
+            // Whether the clause is required is up to `implicit_expr`.
+            Operator::Required => primary_expr(rest, options),
+            Operator::And | Operator::Or => Err(error(input, Expected::Term)),
         }
-        Ok((&input[1..], query))
     } else {
-        let (word, input) = match input.find(|c: char| c.is_whitespace() || c == '(' || c == ')') {
-            Some(idx) => input.split_at(idx),
-            None => (input, ""),
+        let (word, rest) = split_word(input);
+        if word.is_empty() {
+            return Err(error(input, Expected::Term));
+        }
+        // A backslash at the end of the input escapes nothing.
+        let unescape = |word| unescape(word).ok_or_else(|| error(rest, Expected::EscapedChar));
+
+        if let Some(prefix) = word
+            .strip_suffix('*')
+            .filter(|prefix| !ends_with_escape(prefix))
+        {
+            if prefix.is_empty() {
+                return Err(error(input, Expected::Term));
+            }
+            Ok((rest, Query::Prefix(unescape(prefix)?)))
+        } else if let Some((word, distance)) = word.rsplit_once('~').filter(|(word, distance)| {
+            !ends_with_escape(word) && distance.chars().all(|c| c.is_ascii_digit())
+        }) {
+            if word.is_empty() {
+                return Err(error(input, Expected::Term));
+            }
+            let distance_input = &input[word.len() + 1..];
+            match distance.parse() {
+                Ok(distance) if distance <= MAX_FUZZY_DISTANCE => {
+                    Ok((rest, Query::Fuzzy(unescape(word)?, distance)))
+                }
+                _ => Err(error(distance_input, Expected::FuzzyDistance)),
+            }
+        } else {
+            Ok((rest, Query::Word(unescape(word)?)))
+        }
+    }
+}
+
+fn near_expr(input: &str, options: ParseOptions) -> Result<(&str, Query), Error<'_>> {
+    let lhs_input = input.trim_start();
+    let (input, lhs) = primary_expr(lhs_input, options)?;
+    let (operator, rest) = split_word(input.trim_start());
+    if let Some(distance) = operator.strip_prefix("NEAR/") {
+        let distance_input = &input.trim_start()["NEAR/".len()..];
+        let distance = match distance.parse() {
+            Ok(distance) if distance_input.starts_with(|c: char| c.is_ascii_digit()) => distance,
+            _ => return Err(error(distance_input, Expected::NearDistance)),
         };
-        Ok((input, Query::Word(word.to_string())))
+        let rhs_input = rest.trim_start();
+        let (rest, rhs) = primary_expr(rhs_input, options)?;
+        match (lhs, rhs) {
+            (Query::Word(lhs), Query::Word(rhs)) => Ok((rest, Query::Near(lhs, rhs, distance))),
+            (Query::Word(_), _) => Err(error(rhs_input, Expected::Word)),
+            _ => Err(error(lhs_input, Expected::Word)),
+        }
+    } else {
+        Ok((input, lhs))
     }
 }
 
-fn or_expr(input: &str) -> Result<(&str, Query), ParseError> {
-    let (input, lhs) = primary_expr(input)?;
+fn or_expr(input: &str, options: ParseOptions) -> Result<(&str, Query), Error<'_>> {
+    let (input, lhs) = match options.precedence {
+        Precedence::Legacy => near_expr(input, options)?,
+        Precedence::Standard => and_expr(input, options)?,
+    };
     let input = input.trim_start();
-    if let Some(input) = input.strip_prefix("OR") {
-        let (input, rhs) = or_expr(input)?;
+    if let Some((Operator::Or, input)) = split_operator(input) {
+        let (input, rhs) = or_expr(input, options)?;
         Ok((input, Query::Or(Box::new(lhs), Box::new(rhs))))
     } else {
         Ok((input, lhs))
     }
 }
 
-fn and_expr(input: &str) -> Result<(&str, Query), ParseError> {
-    let (input, lhs) = or_expr(input)?;
+/// Parses the clauses juxtaposed without an operator like `rust async runtime`, and combines them
+/// with the default operator.
+fn implicit_expr(input: &str, options: ParseOptions) -> Result<(&str, Query), Error<'_>> {
+    let clause_expr = match options.precedence {
+        Precedence::Legacy => or_expr,
+        Precedence::Standard => near_expr,
+    };
+
+    let is_required = |input: &str| matches!(split_operator(input), Some((Operator::Required, _)));
+
+    let mut input = input.trim_start();
+    let mut clauses = vec![];
+    loop {
+        let required = is_required(input);
+        let (rest, clause) = clause_expr(input, options)?;
+        clauses.push((clause, required));
+
+        input = rest.trim_start();
+        if input.is_empty()
+            || input.starts_with(')')
+            || matches!(
+                split_operator(input),
+                Some((Operator::And | Operator::Or, _))
+            )
+        {
+            break;
+        }
+    }
+    Ok((input, options.default_operator.combine(clauses)))
+}
+
+fn and_expr(input: &str, options: ParseOptions) -> Result<(&str, Query), Error<'_>> {
+    let (input, lhs) = implicit_expr(input, options)?;
     let input = input.trim_start();
-    if let Some(input) = input.strip_prefix("AND") {
-        let (input, rhs) = and_expr(input)?;
+    if let Some((Operator::And, input)) = split_operator(input) {
+        let (input, rhs) = and_expr(input, options)?;
         Ok((input, Query::And(Box::new(lhs), Box::new(rhs))))
     } else {
         Ok((input, lhs))
     }
 }
 
-fn expr(input: &str) -> Result<(&str, Query), ParseError> {
-    and_expr(input)
+fn expr(input: &str, options: ParseOptions) -> Result<(&str, Query), Error<'_>> {
+    match options.precedence {
+        Precedence::Legacy => and_expr(input, options),
+        Precedence::Standard => or_expr(input, options),
+    }
 }
 
 pub fn parse(input: &str) -> Result<Query, ParseError> {
-    let (input, query) = expr(input)?;
-    if input.trim_end().is_empty() {
-        Ok(query)
-    } else {
-        Err(ParseError)
-    }
+    parse_with_options(input, ParseOptions::default())
+}
+
+pub fn parse_with_options(input: &str, options: ParseOptions) -> Result<Query, ParseError> {
+    let result = expr(input, options).and_then(|(rest, query)| {
+        if rest.trim_end().is_empty() {
+            Ok(query)
+        } else {
+            Err(error(rest, Expected::Operator))
+        }
+    });
+
+    result.map_err(|Error { rest, expected }| {
+        let rest = rest.trim_start();
+        let found = match rest.chars().next() {
+            None => None,
+            Some(c @ ('(' | ')')) => Some(c.to_string()),
+            Some(_) => Some(split_word(rest).0.to_string()),
+        };
+        ParseError {
+            offset: input.len() - rest.len(),
+            found,
+            expected,
+        }
+    })
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
+    fn error(offset: usize, found: Option<&str>, expected: Expected) -> ParseError {
+        ParseError {
+            offset,
+            found: found.map(str::to_string),
+            expected,
+        }
+    }
+
     #[test]
     fn test_or() {
         assert_eq!(
@@ -144,6 +799,475 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_not() {
+        assert_eq!(
+            parse("NOT foo").unwrap(),
+            Query::Not(Box::new(Query::Word("foo".to_string())))
+        );
+
+        assert_eq!(
+            parse("-foo").unwrap(),
+            Query::Not(Box::new(Query::Word("foo".to_string())))
+        );
+
+        assert_eq!(
+            parse("foo AND NOT bar").unwrap(),
+            Query::And(
+                Box::new(Query::Word("foo".to_string())),
+                Box::new(Query::Not(Box::new(Query::Word("bar".to_string()))))
+            )
+        );
+
+        assert_eq!(
+            parse("foo AND -(bar OR baz)").unwrap(),
+            Query::And(
+                Box::new(Query::Word("foo".to_string())),
+                Box::new(Query::Not(Box::new(Query::Or(
+                    Box::new(Query::Word("bar".to_string())),
+                    Box::new(Query::Word("baz".to_string()))
+                ))))
+            )
+        );
+
+        assert_eq!(
+            parse("NOT foo AND bar").unwrap(),
+            Query::And(
+                Box::new(Query::Not(Box::new(Query::Word("foo".to_string())))),
+                Box::new(Query::Word("bar".to_string()))
+            )
+        );
+
+        assert_eq!(
+            parse("NOTHING").unwrap(),
+            Query::Word("NOTHING".to_string())
+        );
+        assert_eq!(
+            parse("foo-bar").unwrap(),
+            Query::Word("foo-bar".to_string())
+        );
+    }
+
+    #[test]
+    fn test_phrase() {
+        assert_eq!(
+            parse("\"show hn\"").unwrap(),
+            Query::Phrase(vec![Some("show".to_string()), Some("hn".to_string())])
+        );
+
+        assert_eq!(
+            parse("\" machine  learning \" AND rust").unwrap(),
+            Query::And(
+                Box::new(Query::Phrase(vec![
+                    Some("machine".to_string()),
+                    Some("learning".to_string())
+                ])),
+                Box::new(Query::Word("rust".to_string()))
+            )
+        );
+
+        assert_eq!(parse("\"rust\"").unwrap(), Query::Word("rust".to_string()));
+
+        assert_eq!(
+            parse("\"state ? ? art\"").unwrap(),
+            Query::Phrase(vec![
+                Some("state".to_string()),
+                None,
+                None,
+                Some("art".to_string())
+            ])
+        );
+        assert_eq!(
+            parse("\"? why \\? ?\"").unwrap(),
+            Query::Phrase(vec![Some("why".to_string()), Some("?".to_string())])
+        );
+        assert_eq!(
+            parse("\"? rust\"").unwrap(),
+            Query::Word("rust".to_string())
+        );
+        assert_eq!(parse("\"\\?\"").unwrap(), Query::Word("?".to_string()));
+        assert_eq!(parse("\"? ?\""), Err(error(0, Some("\"?"), Expected::Term)));
+
+        assert_eq!(
+            parse("\"show hn"),
+            Err(error(8, None, Expected::CloseQuote))
+        );
+        assert_eq!(parse("\" \""), Err(error(0, Some("\""), Expected::Term)));
+    }
+
+    #[test]
+    fn test_near() {
+        assert_eq!(
+            parse("rust NEAR/3 compiler").unwrap(),
+            Query::Near("rust".to_string(), "compiler".to_string(), 3)
+        );
+
+        assert_eq!(
+            parse("rust NEAR/3 compiler OR gcc").unwrap(),
+            Query::Or(
+                Box::new(Query::Near("rust".to_string(), "compiler".to_string(), 3)),
+                Box::new(Query::Word("gcc".to_string()))
+            )
+        );
+
+        assert_eq!(
+            parse("rust NEAR/ compiler"),
+            Err(error(11, Some("compiler"), Expected::NearDistance))
+        );
+        assert_eq!(
+            parse("rust NEAR/x compiler"),
+            Err(error(10, Some("x"), Expected::NearDistance))
+        );
+        assert_eq!(
+            parse("rust NEAR/3 (compiler OR gcc)"),
+            Err(error(12, Some("("), Expected::Word))
+        );
+    }
+
+    #[test]
+    fn test_prefix() {
+        assert_eq!(parse("rust*").unwrap(), Query::Prefix("rust".to_string()));
+
+        assert_eq!(
+            parse("(web* OR http*) AND rust").unwrap(),
+            Query::And(
+                Box::new(Query::Or(
+                    Box::new(Query::Prefix("web".to_string())),
+                    Box::new(Query::Prefix("http".to_string()))
+                )),
+                Box::new(Query::Word("rust".to_string()))
+            )
+        );
+
+        assert_eq!(parse("*"), Err(error(0, Some("*"), Expected::Term)));
+    }
+
+    #[test]
+    fn test_fuzzy() {
+        assert_eq!(
+            parse("kubernetes~1").unwrap(),
+            Query::Fuzzy("kubernetes".to_string(), 1)
+        );
+
+        assert_eq!(
+            parse("kubernetes~2 AND docker").unwrap(),
+            Query::And(
+                Box::new(Query::Fuzzy("kubernetes".to_string(), 2)),
+                Box::new(Query::Word("docker".to_string()))
+            )
+        );
+
+        assert_eq!(
+            parse("~/.bashrc").unwrap(),
+            Query::Word("~/.bashrc".to_string())
+        );
+        assert_eq!(
+            parse("kubernetes~"),
+            Err(error(11, None, Expected::FuzzyDistance))
+        );
+        assert_eq!(
+            parse("kubernetes~3"),
+            Err(error(11, Some("3"), Expected::FuzzyDistance))
+        );
+        assert_eq!(parse("~1"), Err(error(0, Some("~1"), Expected::Term)));
+    }
+
+    #[test]
+    fn test_regex() {
+        assert_eq!(
+            parse("/gpt-?[0-9]+/").unwrap(),
+            Query::Regex("gpt-?[0-9]+".to_string())
+        );
+
+        assert_eq!(
+            parse("/(llama|alpaca)/ AND /gpt\\d/").unwrap(),
+            Query::And(
+                Box::new(Query::Regex("(llama|alpaca)".to_string())),
+                Box::new(Query::Regex("gpt\\d".to_string()))
+            )
+        );
+
+        assert_eq!(
+            parse("/tcp\\/ip/").unwrap(),
+            Query::Regex("tcp/ip".to_string())
+        );
+
+        assert_eq!(parse("//"), Err(error(0, Some("//"), Expected::Term)));
+        assert_eq!(parse("/gpt"), Err(error(4, None, Expected::CloseSlash)));
+        assert_eq!(parse("tcp/ip").unwrap(), Query::Word("tcp/ip".to_string()));
+    }
+
+    #[test]
+    fn test_escape() {
+        let word = |word: &str| Box::new(Query::Word(word.to_string()));
+        assert_eq!(parse("\\(2019\\)"), Ok(Query::Word("(2019)".to_string())));
+        assert_eq!(parse("\"(2019)\""), Ok(Query::Word("(2019)".to_string())));
+        assert_eq!(
+            parse("\\AND OR \"OR\""),
+            Ok(Query::Or(word("AND"), word("OR")))
+        );
+        assert_eq!(
+            parse("\"say \\\"hi\\\"\""),
+            Ok(Query::Phrase(vec![
+                Some("say".to_string()),
+                Some("\"hi\"".to_string())
+            ]))
+        );
+        assert_eq!(
+            parse("c\\+\\+ rust\\* a\\~1 \\-java"),
+            Ok(Query::And(
+                word("c++"),
+                Box::new(Query::And(
+                    word("rust*"),
+                    Box::new(Query::And(word("a~1"), word("-java")))
+                ))
+            ))
+        );
+        assert_eq!(
+            parse("new\\ york*"),
+            Ok(Query::Prefix("new york".to_string()))
+        );
+        assert_eq!(parse("c\\\\~1"), Ok(Query::Fuzzy("c\\".to_string(), 1)));
+
+        assert_eq!(parse("rust\\"), Err(error(5, None, Expected::EscapedChar)));
+        assert_eq!(
+            parse("\"rust\\"),
+            Err(error(6, None, Expected::EscapedChar))
+        );
+    }
+
+    #[test]
+    fn test_keyword_tokens() {
+        let word = |word: &str| Box::new(Query::Word(word.to_string()));
+        assert_eq!(parse("ORACLE"), Ok(Query::Word("ORACLE".to_string())));
+        assert_eq!(
+            parse("foo ORACLE"),
+            Ok(Query::And(word("foo"), word("ORACLE")))
+        );
+        assert_eq!(
+            parse("ANDROID OR NOTE"),
+            Ok(Query::Or(word("ANDROID"), word("NOTE")))
+        );
+        assert_eq!(
+            parse("foo AND(bar) OR(baz)"),
+            Ok(Query::And(
+                word("foo"),
+                Box::new(Query::Or(word("bar"), word("baz")))
+            ))
+        );
+        assert_eq!(parse("NEAR/3x"), Ok(Query::Word("NEAR/3x".to_string())));
+        assert_eq!(
+            parse("rust NEAR/3x compiler"),
+            Err(error(10, Some("3x"), Expected::NearDistance))
+        );
+        assert_eq!(
+            parse("rust NEAR/+3 compiler"),
+            Err(error(10, Some("+3"), Expected::NearDistance))
+        );
+    }
+
+    #[test]
+    fn test_aliases() {
+        let word = |word: &str| Box::new(Query::Word(word.to_string()));
+        assert_eq!(parse("rust && (go || zig)"), parse("rust AND (go OR zig)"));
+        assert_eq!(parse("rust&&(go||zig)"), parse("rust AND (go OR zig)"));
+        assert_eq!(parse("rust and go"), parse("rust AND go"));
+        assert_eq!(parse("rust And go"), parse("rust AND go"));
+        assert_eq!(parse("rust or go"), parse("rust OR go"));
+        assert_eq!(parse("rust Or go"), parse("rust OR go"));
+        assert_eq!(parse("+must -mustnot"), parse("must AND NOT mustnot"));
+        assert_eq!(parse("not rust"), Ok(Query::And(word("not"), word("rust"))));
+        assert_eq!(parse("r&d"), Ok(Query::Word("r&d".to_string())));
+        assert_eq!(parse("c++"), Ok(Query::Word("c++".to_string())));
+
+        let options = ParseOptions {
+            default_operator: DefaultOperator::Or,
+            ..ParseOptions::default()
+        };
+        assert_eq!(
+            parse_with_options("rust go +async -java", options),
+            Ok(Query::And(
+                Box::new(Query::Or(word("rust"), word("go"))),
+                Box::new(Query::And(
+                    word("async"),
+                    Box::new(Query::Not(word("java")))
+                ))
+            ))
+        );
+        assert_eq!(
+            parse_with_options("+rust +go", options),
+            Ok(Query::And(word("rust"), word("go")))
+        );
+
+        assert_eq!(parse("rust &&"), Err(error(7, None, Expected::Term)));
+        assert_eq!(
+            parse("rust || && go"),
+            Err(error(8, Some("&&"), Expected::Term))
+        );
+        assert_eq!(parse("+"), Err(error(1, None, Expected::Term)));
+    }
+
+    #[test]
+    fn test_exact() {
+        let word = |word: &str| Box::new(Query::Word(word.to_string()));
+        assert_eq!(parse("=running"), Ok(Query::Exact("running".to_string())));
+        assert_eq!(
+            parse("running -=runs"),
+            Ok(Query::And(
+                word("running"),
+                Box::new(Query::Not(Box::new(Query::Exact("runs".to_string()))))
+            ))
+        );
+        assert_eq!(
+            parse("title:=\"AND\""),
+            Ok(Query::Field(
+                "title".to_string(),
+                Box::new(Query::Exact("AND".to_string()))
+            ))
+        );
+        assert_eq!(parse("a=b"), Ok(Query::Word("a=b".to_string())));
+
+        assert_eq!(parse("=run*"), Err(error(1, Some("run*"), Expected::Word)));
+        assert_eq!(parse("="), Err(error(1, None, Expected::Term)));
+    }
+
+    #[test]
+    fn test_field() {
+        assert_eq!(
+            parse("by:pg AND title:lisp").unwrap(),
+            Query::And(
+                Box::new(Query::Field(
+                    "by".to_string(),
+                    Box::new(Query::Word("pg".to_string()))
+                )),
+                Box::new(Query::Field(
+                    "title".to_string(),
+                    Box::new(Query::Word("lisp".to_string()))
+                ))
+            )
+        );
+
+        assert_eq!(
+            parse("url:github.com").unwrap(),
+            Query::Field(
+                "url".to_string(),
+                Box::new(Query::Word("github.com".to_string()))
+            )
+        );
+
+        assert_eq!(
+            parse("NOT title:(\"show hn\" OR ask*)").unwrap(),
+            Query::Not(Box::new(Query::Field(
+                "title".to_string(),
+                Box::new(Query::Or(
+                    Box::new(Query::Phrase(vec![
+                        Some("show".to_string()),
+                        Some("hn".to_string())
+                    ])),
+                    Box::new(Query::Prefix("ask".to_string()))
+                ))
+            )))
+        );
+
+        assert_eq!(
+            parse("url:https://example.com").unwrap(),
+            Query::Field(
+                "url".to_string(),
+                Box::new(Query::Word("https://example.com".to_string()))
+            )
+        );
+
+        assert_eq!(parse("hn:").unwrap(), Query::Word("hn:".to_string()));
+        assert_eq!(
+            parse("https://github.com").unwrap(),
+            Query::Word("https://github.com".to_string())
+        );
+        assert_eq!(
+            parse("re:invent").unwrap(),
+            Query::Word("re:invent".to_string())
+        );
+        assert_eq!(
+            parse("ratio:1..2").unwrap(),
+            Query::Word("ratio:1..2".to_string())
+        );
+        assert_eq!(parse("Ask:HN").unwrap(), Query::Word("Ask:HN".to_string()));
+    }
+
+    #[test]
+    fn test_range() {
+        assert_eq!(
+            parse("rust AND score:>=100 AND date:2020-01-01..2021-12-31").unwrap(),
+            Query::And(
+                Box::new(Query::Word("rust".to_string())),
+                Box::new(Query::And(
+                    Box::new(Query::Range(
+                        "score".to_string(),
+                        RangeBound::Included("100".to_string()),
+                        RangeBound::Unbounded
+                    )),
+                    Box::new(Query::Range(
+                        "date".to_string(),
+                        RangeBound::Included("2020-01-01".to_string()),
+                        RangeBound::Included("2021-12-31".to_string())
+                    ))
+                ))
+            )
+        );
+
+        assert_eq!(
+            parse("score:>10").unwrap(),
+            Query::Range(
+                "score".to_string(),
+                RangeBound::Excluded("10".to_string()),
+                RangeBound::Unbounded
+            )
+        );
+        assert_eq!(
+            parse("score:<=10").unwrap(),
+            Query::Range(
+                "score".to_string(),
+                RangeBound::Unbounded,
+                RangeBound::Included("10".to_string())
+            )
+        );
+        assert_eq!(
+            parse("(score:<10)").unwrap(),
+            Query::Range(
+                "score".to_string(),
+                RangeBound::Unbounded,
+                RangeBound::Excluded("10".to_string())
+            )
+        );
+        assert_eq!(
+            parse("date:..2008-12-31").unwrap(),
+            Query::Range(
+                "date".to_string(),
+                RangeBound::Unbounded,
+                RangeBound::Included("2008-12-31".to_string())
+            )
+        );
+        assert_eq!(
+            parse("date:2020-01-01..").unwrap(),
+            Query::Range(
+                "date".to_string(),
+                RangeBound::Included("2020-01-01".to_string()),
+                RangeBound::Unbounded
+            )
+        );
+
+        assert_eq!(parse("score:>="), Err(error(8, None, Expected::RangeValue)));
+        assert_eq!(parse("date:.."), Err(error(7, None, Expected::RangeValue)));
+
+        for input in ["url:a..b", "text:...", "url:example.com/..", "title:..net"] {
+            let (field, value) = input.split_once(':').unwrap();
+            assert_eq!(
+                parse(input).unwrap(),
+                Query::Field(field.to_string(), Box::new(Query::Word(value.to_string()))),
+                "{input}"
+            );
+        }
+    }
+
     #[test]
     fn test_precedence() {
         assert_eq!(
@@ -168,4 +1292,245 @@ mod test {
             )
         );
     }
+
+    #[test]
+    fn test_parse_error() {
+        assert_eq!(parse(""), Err(error(0, None, Expected::Term)));
+        assert_eq!(parse("  "), Err(error(2, None, Expected::Term)));
+        assert_eq!(parse("rust AND"), Err(error(8, None, Expected::Term)));
+        assert_eq!(parse("rust OR )"), Err(error(8, Some(")"), Expected::Term)));
+        assert_eq!(
+            parse("(rust OR go"),
+            Err(error(11, None, Expected::CloseParen))
+        );
+        assert_eq!(parse("rust)"), Err(error(4, Some(")"), Expected::Operator)));
+        assert_eq!(parse("NOT -"), Err(error(5, None, Expected::Term)));
+
+        assert_eq!(
+            error(8, None, Expected::Term).to_string(),
+            "expected a term, found the end of the query"
+        );
+        assert_eq!(
+            error(4, Some(")"), Expected::Operator).to_string(),
+            "expected `AND`, `OR` or the end of the query, found `)`"
+        );
+    }
+
+    #[test]
+    fn test_implicit() {
+        let word = |word: &str| Box::new(Query::Word(word.to_string()));
+        assert_eq!(
+            parse("rust async runtime"),
+            Ok(Query::And(
+                word("rust"),
+                Box::new(Query::And(word("async"), word("runtime")))
+            ))
+        );
+        assert_eq!(
+            parse("(rust async) AND tokio"),
+            Ok(Query::And(
+                Box::new(Query::And(word("rust"), word("async"))),
+                word("tokio")
+            ))
+        );
+        assert_eq!(
+            parse("rust go OR zig -java"),
+            Ok(Query::And(
+                word("rust"),
+                Box::new(Query::And(
+                    Box::new(Query::Or(word("go"), word("zig"))),
+                    Box::new(Query::Not(word("java")))
+                ))
+            ))
+        );
+
+        let options = ParseOptions {
+            default_operator: DefaultOperator::Or,
+            ..ParseOptions::default()
+        };
+        assert_eq!(
+            parse_with_options("rust async runtime", options),
+            Ok(Query::Or(
+                word("rust"),
+                Box::new(Query::Or(word("async"), word("runtime")))
+            ))
+        );
+        assert_eq!(
+            parse_with_options("rust -java go NOT c", options),
+            Ok(Query::And(
+                Box::new(Query::Or(word("rust"), word("go"))),
+                Box::new(Query::And(
+                    Box::new(Query::Not(word("java"))),
+                    Box::new(Query::Not(word("c")))
+                ))
+            ))
+        );
+        assert_eq!(
+            parse_with_options("-java -c", options),
+            Ok(Query::And(
+                Box::new(Query::Not(word("java"))),
+                Box::new(Query::Not(word("c")))
+            ))
+        );
+    }
+
+    #[test]
+    fn test_standard_precedence() {
+        let word = |word: &str| Box::new(Query::Word(word.to_string()));
+        let options = ParseOptions {
+            precedence: Precedence::Standard,
+            ..ParseOptions::default()
+        };
+
+        assert_eq!(
+            parse_with_options("foo AND bar OR baz", options),
+            Ok(Query::Or(
+                Box::new(Query::And(word("foo"), word("bar"))),
+                word("baz")
+            ))
+        );
+        assert_eq!(
+            parse_with_options("foo OR bar AND baz", options),
+            Ok(Query::Or(
+                word("foo"),
+                Box::new(Query::And(word("bar"), word("baz")))
+            ))
+        );
+        assert_eq!(
+            parse_with_options("foo bar OR baz -qux", options),
+            Ok(Query::Or(
+                Box::new(Query::And(word("foo"), word("bar"))),
+                Box::new(Query::And(word("baz"), Box::new(Query::Not(word("qux")))))
+            ))
+        );
+        assert_eq!(
+            parse_with_options("(foo OR bar) AND baz", options),
+            Ok(Query::And(
+                Box::new(Query::Or(word("foo"), word("bar"))),
+                word("baz")
+            ))
+        );
+        assert_eq!(
+            parse_with_options("foo AND (bar OR baz)", options),
+            parse("foo AND bar OR baz")
+        );
+        assert_eq!(
+            parse_with_options("foo OR", options),
+            Err(error(6, None, Expected::Term))
+        );
+
+        assert_eq!(
+            format!("{options:?}"),
+            "ParseOptions { default_operator: And, precedence: Standard }"
+        );
+        assert_eq!(
+            options.to_string(),
+            "default operator AND, standard (AND binds tighter than OR) precedence"
+        );
+        assert_eq!(
+            ParseOptions::default().to_string(),
+            "default operator AND, legacy (OR binds tighter than AND) precedence"
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        let display = |input: &str| parse(input).unwrap().to_string();
+        assert_eq!(display("rust   async"), "rust AND async");
+        assert_eq!(display("(a AND b) AND c"), "(a AND b) AND c");
+        assert_eq!(display("a AND (b AND c)"), "a AND b AND c");
+        assert_eq!(display("(a OR b) AND c"), "(a OR b) AND c");
+        assert_eq!(display("a AND b OR c"), "a AND (b OR c)");
+        assert_eq!(display("a OR (b AND c)"), "a OR (b AND c)");
+        assert_eq!(display("NOT (a OR b)"), "-(a OR b)");
+        assert_eq!(
+            display("\"show  hn\" \"NEAR/3\""),
+            "\"show hn\" AND \"NEAR/3\""
+        );
+        assert_eq!(display("title:(rust OR go)"), "title:(rust OR go)");
+        assert_eq!(display("score:\">=5\""), "score:(>=5)");
+        assert_eq!(
+            display("date:2020-01-01..2020-12-31"),
+            "date:2020-01-01..2020-12-31"
+        );
+        assert_eq!(display("x (ORACLE*)"), "x AND ORACLE*");
+        assert_eq!(display("x \"AND\""), "x AND \"AND\"");
+        assert_eq!(display("/a\\/b/"), "/a\\/b/");
+        assert_eq!(display("\\(2019\\)"), "\"(2019)\"");
+        assert_eq!(display("c\\+\\+* new\\ york~1"), "c++* AND new\\ york~1");
+
+        let inputs = [
+            "rust",
+            "-rust",
+            "NOT NOT rust",
+            "\"rust\"",
+            "\"-rust\"",
+            "\"rust*\"",
+            "\"c~1\"",
+            "\"hn:\"",
+            "\"ask:hn\"",
+            "\"OR\" NEAR/1 \"NOT\"",
+            "\"NEAR/1\"",
+            "rust NEAR/5 compiler",
+            "\"async\" NEAR/5 \"ORM\"",
+            "kubernetes~2 OR kube*",
+            "/\\d+/ /a\\\\/",
+            "by:pg AND -title:\"ask hn\"",
+            "by:-pg",
+            "by:(url:rust)",
+            "title:(score:>5)",
+            "score:>=100 score:<5 score:..10 score:1.. score:>5",
+            "date:2020-01-01..",
+            "(a AND b) OR (c AND d) AND e",
+            "a OR b OR (c OR d)",
+            "((a OR b) OR c) d",
+            "-(a NEAR/2 b) OR -(c d)",
+            "Ask:HN ANDROID",
+            "a (ORACLE~1) (NOT*)",
+            "\\(2019\\) \\AND \\NOT",
+            "\"say \\\"hi\\\"\" \"a\\\\b c\"",
+            "\"state ? ? art\" \"\\? ?\\? ? ?? \\\\?\"",
+            "(a\\*~1) \\-b* \\/c\\/* by\\:x*",
+            "new\\ york \"new\\ york\" NEAR/2 \\\"",
+            "\"and\" \"||\" a\\&&b* \\+c~1 \"+d\"",
+            "=run \"=run\" =\"-run\" =\"run*\" \\=a* title:=\"OR\"",
+            "re:invent https://github.com title:re:invent url:http://a.b",
+        ];
+        let standard = ParseOptions {
+            precedence: Precedence::Standard,
+            ..ParseOptions::default()
+        };
+        for input in inputs {
+            for options in [ParseOptions::default(), standard] {
+                let query = parse_with_options(input, options).unwrap();
+                // The canonical form does not depend on the precedence it is parsed with.
+                for reparse_options in [ParseOptions::default(), standard] {
+                    assert_eq!(
+                        parse_with_options(&query.to_string(), reparse_options),
+                        Ok(query.clone()),
+                        "{input}"
+                    );
+                }
+            }
+        }
+
+        let range = Query::Range(
+            "score".to_string(),
+            RangeBound::Excluded("1".to_string()),
+            RangeBound::Included("5".to_string()),
+        );
+        assert_eq!(range.to_string(), "(score:>1 AND score:<=5)");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let query = parse("by:pg score:>=100 -\"ask hn\"").unwrap();
+        let json = serde_json::to_string(&query).unwrap();
+        assert_eq!(
+            json,
+            r#"{"And":[{"Field":["by",{"Word":"pg"}]},{"And":[{"Range":["score",{"Included":"100"},"Unbounded"]},{"Not":{"Phrase":["ask","hn"]}}]}]}"#
+        );
+        assert_eq!(serde_json::from_str::<Query>(&json).unwrap(), query);
+    }
 }
@@ -0,0 +1,233 @@
+//! Rewriting of queries into a canonical form that is cheaper to evaluate.
+
+use std::fmt;
+
+use crate::Query;
+
+/// A query rewritten by `Query::normalize`.
+///
+/// The boolean operators are n-ary with deduplicated and sorted operands, and fields are pushed down
+/// to the terms so that `by:(pg OR sama)` becomes `by:pg OR by:sama`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum NormalizedQuery {
+    /// A query without boolean operators like a word or a range, possibly in a field like `by:pg`.
+    Term(Query),
+    Not(Box<NormalizedQuery>),
+    /// Two or more queries that must all match.
+    And(Vec<NormalizedQuery>),
+    /// Two or more queries any of which must match.
+    Or(Vec<NormalizedQuery>),
+}
+
+impl Query {
+    /// Rewrites the query into an equivalent normalized query.
+    ///
+    /// Nested conjunctions and disjunctions are flattened, duplicated operands are removed, the
+    /// absorption laws (`a AND (a OR b)` is `a`, and `a OR (a AND b)` is `a`) and the double
+    /// negation law are applied, and the operands are sorted.
+    pub fn normalize(&self) -> NormalizedQuery {
+        normalize(self, None)
+    }
+}
+
+fn normalize(query: &Query, field: Option<&str>) -> NormalizedQuery {
+    match query {
+        // The innermost field takes precedence.
+        Query::Field(name, query) => normalize(query, Some(name)),
+        Query::And(lhs, rhs) => {
+            NormalizedQuery::connective(true, vec![normalize(lhs, field), normalize(rhs, field)])
+        }
+        Query::Or(lhs, rhs) => {
+            NormalizedQuery::connective(false, vec![normalize(lhs, field), normalize(rhs, field)])
+        }
+        Query::Not(query) => match normalize(query, field) {
+            NormalizedQuery::Not(query) => *query,
+            query => NormalizedQuery::Not(Box::new(query)),
+        },
+        // Ranges name their own fields.
+        Query::Range(_, _, _) => NormalizedQuery::Term(query.clone()),
+        query => match field {
+            Some(name) => {
+                NormalizedQuery::Term(Query::Field(name.to_string(), Box::new(query.clone())))
+            }
+            None => NormalizedQuery::Term(query.clone()),
+        },
+    }
+}
+
+impl NormalizedQuery {
+    /// Returns the operands if this is a conjunction (`and` is true) or a disjunction (`and` is
+    /// false).
+    fn operands(&self, and: bool) -> Option<&[NormalizedQuery]> {
+        match self {
+            NormalizedQuery::And(queries) if and => Some(queries),
+            NormalizedQuery::Or(queries) if !and => Some(queries),
+            _ => None,
+        }
+    }
+
+    /// Builds the conjunction (`and` is true) or the disjunction (`and` is false) of the normalized
+    /// queries.
+    fn connective(and: bool, queries: Vec<NormalizedQuery>) -> NormalizedQuery {
+        let mut operands = vec![];
+        for query in queries {
+            match query {
+                NormalizedQuery::And(queries) if and => operands.extend(queries),
+                NormalizedQuery::Or(queries) if !and => operands.extend(queries),
+                query => operands.push(query),
+            }
+        }
+        operands.sort();
+        operands.dedup();
+
+        // An operand of the dual connective is redundant if it contains all the operands of another
+        // operand, like `a OR b` in `a AND (a OR b)` or `(a OR b) AND (a OR b OR c)`.
+        let absorbed: Vec<bool> = operands
+            .iter()
+            .map(|operand| {
+                let Some(dual_operands) = operand.operands(!and) else {
+                    return false;
+                };
+                operands.iter().any(|other| {
+                    other != operand
+                        && other
+                            .operands(!and)
+                            .unwrap_or(std::slice::from_ref(other))
+                            .iter()
+                            .all(|query| dual_operands.binary_search(query).is_ok())
+                })
+            })
+            .collect();
+        let mut absorbed = absorbed.into_iter();
+        operands.retain(|_| !absorbed.next().unwrap());
+
+        if operands.len() == 1 {
+            operands.pop().unwrap()
+        } else if and {
+            NormalizedQuery::And(operands)
+        } else {
+            NormalizedQuery::Or(operands)
+        }
+    }
+
+    /// Converts the normalized query back to a query, nesting the binary operators to the right.
+    pub fn to_query(&self) -> Query {
+        fn fold(queries: &[NormalizedQuery], op: fn(Box<Query>, Box<Query>) -> Query) -> Query {
+            queries
+                .iter()
+                .rev()
+                .map(NormalizedQuery::to_query)
+                .reduce(|rhs, lhs| op(Box::new(lhs), Box::new(rhs)))
+                .unwrap()
+        }
+
+        match self {
+            NormalizedQuery::Term(query) => query.clone(),
+            NormalizedQuery::Not(query) => Query::Not(Box::new(query.to_query())),
+            NormalizedQuery::And(queries) => fold(queries, Query::And),
+            NormalizedQuery::Or(queries) => fold(queries, Query::Or),
+        }
+    }
+}
+
+impl fmt::Display for NormalizedQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_query())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    fn normalize(input: &str) -> NormalizedQuery {
+        parse(input).unwrap().normalize()
+    }
+
+    fn term(word: &str) -> NormalizedQuery {
+        NormalizedQuery::Term(Query::Word(word.to_string()))
+    }
+
+    #[test]
+    fn test_flatten() {
+        assert_eq!(
+            normalize("a AND (b AND (c AND d))"),
+            NormalizedQuery::And(vec![term("a"), term("b"), term("c"), term("d")])
+        );
+        assert_eq!(
+            normalize("(a OR b) OR (c OR d)"),
+            NormalizedQuery::Or(vec![term("a"), term("b"), term("c"), term("d")])
+        );
+        assert_eq!(
+            normalize("a (b OR c) d"),
+            NormalizedQuery::And(vec![
+                term("a"),
+                term("d"),
+                NormalizedQuery::Or(vec![term("b"), term("c")])
+            ])
+        );
+    }
+
+    #[test]
+    fn test_dedup_and_sort() {
+        assert_eq!(normalize("rust rust"), term("rust"));
+        assert_eq!(
+            normalize("c b a b"),
+            NormalizedQuery::And(vec![term("a"), term("b"), term("c")])
+        );
+        assert_eq!(normalize("b OR a"), normalize("a OR (b OR a)"));
+        assert_eq!(normalize("(a OR b) (b OR a)"), normalize("a OR b"));
+    }
+
+    #[test]
+    fn test_absorption() {
+        assert_eq!(normalize("a AND (a OR b)"), term("a"));
+        assert_eq!(normalize("a OR (a AND b)"), term("a"));
+        assert_eq!(normalize("(a OR b) (c OR b OR a)"), normalize("a OR b"));
+        assert_eq!(
+            normalize("a b (b OR c) (c OR d)"),
+            NormalizedQuery::And(vec![
+                term("a"),
+                term("b"),
+                NormalizedQuery::Or(vec![term("c"), term("d")])
+            ])
+        );
+        assert_eq!(
+            normalize("-a (-a OR b)"),
+            NormalizedQuery::Not(Box::new(term("a")))
+        );
+    }
+
+    #[test]
+    fn test_not_and_field() {
+        assert_eq!(normalize("NOT -rust"), term("rust"));
+        assert_eq!(
+            normalize("by:(pg OR -sama)"),
+            NormalizedQuery::Or(vec![
+                NormalizedQuery::Term(parse("by:pg").unwrap()),
+                NormalizedQuery::Not(Box::new(NormalizedQuery::Term(parse("by:sama").unwrap()))),
+            ])
+        );
+        assert_eq!(normalize("by:(title:rust)"), normalize("title:rust"));
+        assert_eq!(
+            normalize("title:(score:>=100)"),
+            NormalizedQuery::Term(parse("score:>=100").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_to_query() {
+        for input in [
+            "rust",
+            "c b a",
+            "a OR (b c) OR -d",
+            "by:(pg OR sama) score:>=100",
+            "-(a OR b) AND \"show hn\"",
+        ] {
+            let normalized = normalize(input);
+            assert_eq!(normalize(&normalized.to_string()), normalized, "{input}");
+        }
+        assert_eq!(normalize("c (b OR a)").to_string(), "c AND (a OR b)");
+    }
+}
@@ -0,0 +1,634 @@
+//! The analysis turning the values of the fields into the terms in the index.
+//!
+//! The same analyzer must be used to build the index and to look up the words in queries, so the
+//! indexer records the name of its analyzer in the index and the query side compares it with its own.
+
+use std::{collections::BTreeSet, fmt, str::FromStr};
+
+use caseless::default_case_fold_str;
+use rust_stemmers::{Algorithm, Stemmer};
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::field::Field;
+
+/// The key of the schema metadata of the stored fields recording the name of the analyzer.
+pub const ANALYZER_METADATA_KEY: &str = "anubistats.analyzer";
+
+/// Splits the values of the fields into tokens.
+///
+/// Only the text fields like the titles are split differently by the tokenizers. The other fields
+/// are always split as `Field::tokenize` does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Tokenizer {
+    /// Splits the text fields at whitespaces, keeping the punctuation attached to the words.
+    Whitespace,
+    /// Splits the text fields into words by `segment_words`.
+    #[default]
+    Unicode,
+    /// Splits the text fields into words like `Tokenizer::Unicode`, except that the runs of CJK
+    /// characters are split by `cjk_tokens`.
+    Cjk,
+}
+
+impl Tokenizer {
+    pub fn name(self) -> &'static str {
+        match self {
+            Tokenizer::Whitespace => "whitespace",
+            Tokenizer::Unicode => "unicode",
+            Tokenizer::Cjk => "cjk",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Tokenizer> {
+        match name {
+            "whitespace" => Some(Tokenizer::Whitespace),
+            "unicode" => Some(Tokenizer::Unicode),
+            "cjk" => Some(Tokenizer::Cjk),
+            _ => None,
+        }
+    }
+
+    /// Splits the value of `field` into tokens along with their positions.
+    ///
+    /// A run of CJK characters takes a position for each character, holding the bigram starting
+    /// with it. If `unigrams` is true, the characters are tokens too, sharing the positions with
+    /// the bigrams, as `cjk_tokens` returns them.
+    pub fn tokenize(self, field: Field, value: &str, unigrams: bool) -> Vec<(usize, String)> {
+        let tokens: Vec<(usize, &str)> = match (self, field) {
+            (Tokenizer::Unicode, field) if field.is_text() => {
+                segment_words(value).into_iter().enumerate().collect()
+            }
+            (Tokenizer::Cjk, field) if field.is_text() => {
+                let mut tokens = vec![];
+                let mut offset = 0;
+                for (is_cjk, run) in split_cjk_runs(value) {
+                    if is_cjk {
+                        let run_tokens = cjk_tokens(run, unigrams);
+                        tokens.extend(
+                            run_tokens
+                                .into_iter()
+                                .map(|(position, token)| (offset + position, token)),
+                        );
+                        offset += run.chars().count();
+                    } else {
+                        let words = segment_words(run);
+                        tokens.extend((offset..).zip(words.iter().copied()));
+                        offset += words.len();
+                    }
+                }
+                tokens
+            }
+            _ => field.tokenize(value).into_iter().enumerate().collect(),
+        };
+        tokens
+            .into_iter()
+            .map(|(position, token)| (position, token.to_string()))
+            .collect()
+    }
+
+    /// Returns the number of positions the tokens of the value of `field` take, counting the last
+    /// character of each CJK run, which holds no bigram.
+    pub fn count_positions(self, field: Field, value: &str) -> usize {
+        self.tokenize(field, value, true)
+            .last()
+            .map_or(0, |&(position, _)| position + 1)
+    }
+}
+
+/// Splits `text` into the words between the word boundaries of Unicode Standard Annex #29,
+/// dropping the punctuation and the symbols around them.
+///
+/// Words like `node.js` and version numbers like `1.2.3` are kept whole by the annex. In addition,
+/// the trailing `+` and `#` of words like `c++` and `c#`, and the leading `.` of words like `.net`
+/// are kept since they are part of the names.
+pub fn segment_words(text: &str) -> Vec<&str> {
+    let mut words = vec![];
+    for (start, segment) in text.split_word_bound_indices() {
+        if !segment.chars().any(char::is_alphanumeric) {
+            continue;
+        }
+
+        let mut start = start;
+        let mut end = start + segment.len();
+        if let Some(before) = text[..start].strip_suffix('.') {
+            if !before.ends_with(char::is_alphanumeric) {
+                start -= 1;
+            }
+        }
+        let suffix = text[end..]
+            .find(|c: char| c != '+' && c != '#')
+            .unwrap_or(text.len() - end);
+        if !text[end + suffix..].starts_with(char::is_alphanumeric) {
+            end += suffix;
+        }
+        words.push(&text[start..end]);
+    }
+    words
+}
+
+/// Returns true if `c` is a Chinese, Japanese or Korean character, which are written without
+/// whitespaces between words.
+pub fn is_cjk(c: char) -> bool {
+    matches!(c,
+        // CJK Unified Ideographs and their extensions
+        '\u{3400}'..='\u{4DBF}'
+        | '\u{4E00}'..='\u{9FFF}'
+        | '\u{20000}'..='\u{2FA1F}'
+        // CJK Compatibility Ideographs
+        | '\u{F900}'..='\u{FAFF}'
+        // Ideographic iteration marks like `々`
+        | '\u{3005}'..='\u{3007}'
+        // Hiragana and Katakana, including the half-width ones
+        | '\u{3040}'..='\u{30FF}'
+        | '\u{31F0}'..='\u{31FF}'
+        | '\u{FF66}'..='\u{FF9F}'
+        // Hangul
+        | '\u{1100}'..='\u{11FF}'
+        | '\u{3130}'..='\u{318F}'
+        | '\u{AC00}'..='\u{D7AF}'
+    )
+}
+
+/// Splits `text` into the maximal runs of CJK characters and the text between them, returning
+/// whether each run consists of CJK characters.
+fn split_cjk_runs(text: &str) -> impl Iterator<Item = (bool, &str)> {
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        let is_cjk_run = is_cjk(rest.chars().next()?);
+        let end = rest.find(|c| is_cjk(c) != is_cjk_run).unwrap_or(rest.len());
+        let (run, tail) = rest.split_at(end);
+        rest = tail;
+        Some((is_cjk_run, run))
+    })
+}
+
+/// Splits a run of CJK characters into the overlapping pairs of adjacent characters, like `東京`
+/// and `京都` for `東京都`, since the words in CJK text are not delimited.
+///
+/// A query for a word is analyzed into its bigrams too, and matches as a phrase of them. A run of a
+/// single character is kept as it is, and matches the characters that `cjk_tokens` indexes too.
+pub fn cjk_bigrams(run: &str) -> Vec<&str> {
+    let boundaries: Vec<usize> = run
+        .char_indices()
+        .map(|(idx, _)| idx)
+        .chain([run.len()])
+        .collect();
+    if boundaries.len() <= 2 {
+        return boundaries.windows(2).map(|w| &run[w[0]..w[1]]).collect();
+    }
+    boundaries.windows(3).map(|w| &run[w[0]..w[2]]).collect()
+}
+
+/// Splits a run of CJK characters into tokens along with their positions, which are the indexes of
+/// the characters starting them.
+///
+/// The tokens are the bigrams of `cjk_bigrams`. If `unigrams` is true, as when indexing, each
+/// character is a token as well, so that a query for a single character like `東` matches the
+/// longer runs containing it.
+pub fn cjk_tokens(run: &str, unigrams: bool) -> Vec<(usize, &str)> {
+    let bigrams = cjk_bigrams(run);
+    if !unigrams || bigrams.len() == run.chars().count() {
+        return bigrams.into_iter().enumerate().collect();
+    }
+    run.char_indices()
+        .enumerate()
+        .flat_map(|(position, (idx, c))| {
+            let unigram = (position, &run[idx..idx + c.len_utf8()]);
+            let bigram = bigrams.get(position).map(|&bigram| (position, bigram));
+            std::iter::once(unigram).chain(bigram)
+        })
+        .collect()
+}
+
+/// The stopwords removed by `TokenFilter::Stopwords` unless a list is given, which are the ones
+/// Lucene removes from English text.
+pub const ENGLISH_STOPWORDS: [&str; 33] = [
+    "a", "an", "and", "are", "as", "at", "be", "but", "by", "for", "if", "in", "into", "is", "it",
+    "no", "not", "of", "on", "or", "such", "that", "the", "their", "then", "there", "these",
+    "they", "this", "to", "was", "will", "with",
+];
+
+/// Rewrites or removes each token produced by the tokenizer.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TokenFilter {
+    Lowercase,
+    /// Folds the variants of a word into one term, applying the NFKC normalization, the Unicode
+    /// case folding, and the removal of diacritics in this order.
+    ///
+    /// Full-width characters and ligatures like `ﬁ` are replaced with their compatible characters,
+    /// so that `Ｃａｆé`, `CAFÉ` and `cafe` are all folded into `cafe`.
+    Fold,
+    /// Reduces words like `running` and `runs` to their stem `run` by the Snowball English stemmer.
+    ///
+    /// Only the text fields are stemmed, and their exact fields like `exact_title` keep the words
+    /// unstemmed for exact matching.
+    Stem,
+    /// Removes the listed words, which are compared after the preceding filters are applied.
+    ///
+    /// Only the words in the text fields are removed. The removed words keep their positions, so
+    /// that a phrase like `"state of the art"` matches the words `state` and `art` three words apart.
+    ///
+    /// The filter is named `stopwords` with `ENGLISH_STOPWORDS`, or `stopwords:` followed by the
+    /// comma-separated words otherwise.
+    Stopwords(BTreeSet<String>),
+}
+
+impl TokenFilter {
+    pub fn from_name(name: &str) -> Option<TokenFilter> {
+        match name {
+            "lowercase" => Some(TokenFilter::Lowercase),
+            "fold" => Some(TokenFilter::Fold),
+            "stem" => Some(TokenFilter::Stem),
+            "stopwords" => Some(TokenFilter::english_stopwords()),
+            name => {
+                let words = name.strip_prefix("stopwords:")?;
+                Some(TokenFilter::Stopwords(
+                    words
+                        .split(',')
+                        .filter(|word| !word.is_empty())
+                        .map(str::to_string)
+                        .collect(),
+                ))
+            }
+        }
+    }
+
+    pub fn english_stopwords() -> TokenFilter {
+        TokenFilter::Stopwords(ENGLISH_STOPWORDS.into_iter().map(str::to_string).collect())
+    }
+
+    /// Returns true if the filter rewrites or removes the tokens of `field`.
+    pub fn applies_to(&self, field: Field) -> bool {
+        match self {
+            TokenFilter::Lowercase | TokenFilter::Fold => true,
+            TokenFilter::Stem => field.is_text() && !field.is_exact(),
+            TokenFilter::Stopwords(_) => field.is_text(),
+        }
+    }
+
+    /// Returns true if the filter only normalizes the characters of the tokens, in which case it
+    /// applies to parts of words like the prefixes in queries as well.
+    pub fn is_normalization(&self) -> bool {
+        match self {
+            TokenFilter::Lowercase | TokenFilter::Fold => true,
+            TokenFilter::Stem | TokenFilter::Stopwords(_) => false,
+        }
+    }
+
+    /// Returns the rewritten token, or `None` if the token is removed.
+    pub fn apply(&self, token: String) -> Option<String> {
+        match self {
+            TokenFilter::Lowercase => Some(token.to_lowercase()),
+            TokenFilter::Fold => Some(fold(&token)),
+            TokenFilter::Stem => Some(
+                Stemmer::create(Algorithm::English)
+                    .stem(&token)
+                    .into_owned(),
+            ),
+            TokenFilter::Stopwords(words) => (!words.contains(&token)).then_some(token),
+        }
+    }
+}
+
+impl fmt::Display for TokenFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenFilter::Lowercase => write!(f, "lowercase"),
+            TokenFilter::Fold => write!(f, "fold"),
+            TokenFilter::Stem => write!(f, "stem"),
+            filter @ TokenFilter::Stopwords(words) => {
+                if *filter == TokenFilter::english_stopwords() {
+                    write!(f, "stopwords")
+                } else {
+                    let words: Vec<_> = words.iter().map(String::as_str).collect();
+                    write!(f, "stopwords:{}", words.join(","))
+                }
+            }
+        }
+    }
+}
+
+/// Applies `TokenFilter::Fold` to `token`.
+fn fold(token: &str) -> String {
+    let folded = default_case_fold_str(&token.nfkc().collect::<String>());
+    // Decompose the characters to remove the combining diacritics, and compose the rest again.
+    // The voiced sound marks of kana like `で` are not diacritics, so the marks on CJK characters
+    // are kept.
+    let mut base = None;
+    folded
+        .nfd()
+        .filter(|&c| {
+            if !is_combining_mark(c) {
+                base = Some(c);
+                true
+            } else {
+                base.is_some_and(is_cjk)
+            }
+        })
+        .nfc()
+        .collect()
+}
+
+/// A tokenizer followed by a chain of token filters.
+///
+/// The name of an analyzer lists the tokenizer and the filters joined by `+`, like
+/// `unicode+lowercase+stopwords`, so that the chain can be configured by the name.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Analyzer {
+    pub tokenizer: Tokenizer,
+    pub filters: Vec<TokenFilter>,
+}
+
+impl Default for Analyzer {
+    fn default() -> Self {
+        Self {
+            tokenizer: Tokenizer::default(),
+            filters: vec![TokenFilter::Fold, TokenFilter::english_stopwords()],
+        }
+    }
+}
+
+impl Analyzer {
+    /// Returns the terms to look up for the value of `field` in queries, in the order of their
+    /// positions.
+    pub fn analyze(&self, field: Field, value: &str) -> Vec<String> {
+        self.analyze_with_positions(field, value)
+            .into_iter()
+            .map(|(_, term)| term)
+            .collect()
+    }
+
+    /// Returns the terms to look up for the value of `field` in queries along with their
+    /// positions.
+    ///
+    /// The positions count the tokens removed by the filters like stopwords, so that the distances
+    /// between the terms in phrases and proximities do not depend on the filters.
+    pub fn analyze_with_positions(&self, field: Field, value: &str) -> Vec<(usize, String)> {
+        self.filter_tokens(field, self.tokenizer.tokenize(field, value, false))
+    }
+
+    /// Returns the terms to index for the value of `field` along with their positions.
+    ///
+    /// These are the terms of `analyze_with_positions` and the single CJK characters, which share
+    /// the positions with the bigrams starting with them.
+    pub fn analyze_for_index(&self, field: Field, value: &str) -> Vec<(usize, String)> {
+        self.filter_tokens(field, self.tokenizer.tokenize(field, value, true))
+    }
+
+    fn filter_tokens(&self, field: Field, tokens: Vec<(usize, String)>) -> Vec<(usize, String)> {
+        tokens
+            .into_iter()
+            .filter_map(|(position, token)| {
+                let term = self
+                    .filters
+                    .iter()
+                    .filter(|filter| filter.applies_to(field))
+                    .try_fold(token, |token, filter| filter.apply(token))?;
+                (!term.is_empty()).then_some((position, term))
+            })
+            .collect()
+    }
+
+    /// Applies the normalizing filters to a single token without splitting it, as for prefixes in
+    /// queries, which are matched against the unstemmed words.
+    pub fn filter(&self, token: String) -> String {
+        self.filters
+            .iter()
+            .filter(|filter| filter.is_normalization())
+            .fold(token, |token, filter| filter.apply(token).unwrap())
+    }
+
+    /// Returns true if the words in the text fields are stemmed, in which case their exact fields
+    /// are indexed as well.
+    pub fn stems(&self) -> bool {
+        self.filters.contains(&TokenFilter::Stem)
+    }
+}
+
+impl fmt::Display for Analyzer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.tokenizer.name())?;
+        for filter in self.filters.iter() {
+            write!(f, "+{filter}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Analyzer {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> anyhow::Result<Self> {
+        let mut names = name.split('+');
+        let tokenizer = names.next().unwrap_or_default();
+        let tokenizer = Tokenizer::from_name(tokenizer)
+            .ok_or_else(|| anyhow::anyhow!("unknown tokenizer: {tokenizer}"))?;
+        let filters = names
+            .map(|name| {
+                TokenFilter::from_name(name)
+                    .ok_or_else(|| anyhow::anyhow!("unknown token filter: {name}"))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self { tokenizer, filters })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_segment_words() {
+        assert_eq!(
+            segment_words("Rust, (rust) and rust: \"RUST\""),
+            vec!["Rust", "rust", "and", "rust", "RUST"]
+        );
+        assert_eq!(
+            segment_words("C++ vs. C# vs. .NET (F#/C++)"),
+            vec!["C++", "vs", "C#", "vs", ".NET", "F#", "C++"]
+        );
+        assert_eq!(
+            segment_words("Rust 1.69.0 released; node.js v20.1 is out..."),
+            vec!["Rust", "1.69.0", "released", "node.js", "v20.1", "is", "out"]
+        );
+        assert_eq!(
+            segment_words("a+b 1+1=2 --- +"),
+            vec!["a", "b", "1", "1", "2"]
+        );
+        assert_eq!(
+            segment_words("Café’s naïve résumé"),
+            vec!["Café’s", "naïve", "résumé"]
+        );
+    }
+
+    #[test]
+    fn test_cjk_bigrams() {
+        assert_eq!(cjk_bigrams("東京都"), vec!["東京", "京都"]);
+        assert_eq!(cjk_bigrams("東京"), vec!["東京"]);
+        assert_eq!(cjk_bigrams("東"), vec!["東"]);
+        assert_eq!(cjk_bigrams(""), Vec::<&str>::new());
+
+        let analyzer: Analyzer = "cjk+fold".parse().unwrap();
+        assert_eq!(
+            analyzer.analyze(Field::Title, "Rustで書かれた検索エンジン (2023)"),
+            vec![
+                "rust", "で書", "書か", "かれ", "れた", "た検", "検索", "索エ", "エン", "ンジ",
+                "ジン", "2023"
+            ]
+        );
+        assert_eq!(
+            analyzer.analyze(Field::Title, "한국어 ｶﾀｶﾅ"),
+            vec!["한국", "국어", "カタ", "タカ", "カナ"]
+        );
+        assert_eq!(analyzer.analyze(Field::By, "東京"), vec!["東京"]);
+        assert_eq!(
+            Analyzer::default().analyze(Field::Title, "東京都"),
+            vec!["東", "京", "都"]
+        );
+    }
+
+    #[test]
+    fn test_cjk_tokens() {
+        assert_eq!(cjk_tokens("東京都", false), vec![(0, "東京"), (1, "京都")]);
+        assert_eq!(
+            cjk_tokens("東京都", true),
+            vec![(0, "東"), (0, "東京"), (1, "京"), (1, "京都"), (2, "都")]
+        );
+        assert_eq!(cjk_tokens("東", true), vec![(0, "東")]);
+
+        let analyzer: Analyzer = "cjk+fold".parse().unwrap();
+        let terms = |terms: &[(usize, &str)]| {
+            terms
+                .iter()
+                .map(|&(position, term)| (position, term.to_string()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            analyzer.analyze_with_positions(Field::Title, "東京都 Rust 東"),
+            terms(&[(0, "東京"), (1, "京都"), (3, "rust"), (4, "東")])
+        );
+        assert_eq!(
+            analyzer.analyze_for_index(Field::Title, "東京都 Rust 東"),
+            terms(&[
+                (0, "東"),
+                (0, "東京"),
+                (1, "京"),
+                (1, "京都"),
+                (2, "都"),
+                (3, "rust"),
+                (4, "東")
+            ])
+        );
+        assert_eq!(
+            analyzer
+                .tokenizer
+                .count_positions(Field::Title, "東京都 Rust"),
+            4
+        );
+    }
+
+    #[test]
+    fn test_analyze() {
+        let analyzer = Analyzer::default();
+        assert_eq!(
+            analyzer.analyze(Field::Title, "Show HN:  Rust (2019)"),
+            vec!["show", "hn", "rust", "2019"]
+        );
+        assert_eq!(
+            analyzer.analyze(Field::Url, "https://github.com/rust-lang"),
+            vec!["github.com", "rust-lang"]
+        );
+        assert_eq!(analyzer.analyze(Field::By, " PG "), vec!["pg"]);
+        assert_eq!(analyzer.filter("Rust".to_string()), "rust");
+
+        let analyzer = Analyzer {
+            tokenizer: Tokenizer::Whitespace,
+            filters: vec![],
+        };
+        assert_eq!(
+            analyzer.analyze(Field::Title, "Show HN: Rust"),
+            vec!["Show", "HN:", "Rust"]
+        );
+    }
+
+    #[test]
+    fn test_stem() {
+        let analyzer: Analyzer = "unicode+lowercase+stem".parse().unwrap();
+        assert!(analyzer.stems());
+        assert!(!Analyzer::default().stems());
+        assert_eq!(
+            analyzer.analyze(Field::Title, "Running runs RUN"),
+            vec!["run", "run", "run"]
+        );
+        assert_eq!(
+            analyzer.analyze(Field::ExactTitle, "Running runs RUN"),
+            vec!["running", "runs", "run"]
+        );
+        assert_eq!(analyzer.analyze(Field::By, "runs"), vec!["runs"]);
+        assert_eq!(analyzer.filter("Running".to_string()), "running");
+    }
+
+    #[test]
+    fn test_fold() {
+        let analyzer = Analyzer::default();
+        for (value, terms) in [
+            ("Café CAFÉ cafe\u{301}", vec!["cafe", "cafe", "cafe"]),
+            ("Ｒｕｓｔ ﬁle Straße", vec!["rust", "file", "strasse"]),
+            ("naïve Ångström Łódź", vec!["naive", "angstrom", "łodz"]),
+            ("デジタル ﾃﾞｼﾞﾀﾙ", vec!["デジタル", "デジタル"]),
+        ] {
+            assert_eq!(analyzer.analyze(Field::Title, value), terms, "{value}");
+        }
+        assert_eq!(analyzer.analyze(Field::By, "José"), vec!["jose"]);
+        assert_eq!(analyzer.filter("Ｃａｆé".to_string()), "cafe");
+        assert_eq!(
+            "unicode+lowercase"
+                .parse::<Analyzer>()
+                .unwrap()
+                .filter("Café".to_string()),
+            "café"
+        );
+    }
+
+    #[test]
+    fn test_stopwords() {
+        let analyzer = Analyzer::default();
+        assert_eq!(
+            analyzer.analyze(Field::Title, "The State of the Art"),
+            vec!["state", "art"]
+        );
+        assert_eq!(
+            analyzer.analyze_with_positions(Field::Title, "The State of the Art"),
+            vec![(1, "state".to_string()), (4, "art".to_string())]
+        );
+        assert_eq!(analyzer.analyze(Field::By, "the"), vec!["the"]);
+        assert_eq!(analyzer.filter("The".to_string()), "the");
+
+        let analyzer: Analyzer = "unicode+stopwords:The,of+lowercase".parse().unwrap();
+        assert_eq!(
+            analyzer.analyze(Field::Title, "The State of the Art"),
+            vec!["state", "the", "art"]
+        );
+        assert_eq!(analyzer.to_string(), "unicode+stopwords:The,of+lowercase");
+    }
+
+    #[test]
+    fn test_name() {
+        assert_eq!(Analyzer::default().to_string(), "unicode+fold+stopwords");
+        assert_eq!(
+            "unicode+fold+stopwords".parse::<Analyzer>().unwrap(),
+            Analyzer::default()
+        );
+        assert_eq!("whitespace".parse::<Analyzer>().unwrap().filters, vec![]);
+        assert_eq!(
+            "unicode+lowercase+stopwords+stem"
+                .parse::<Analyzer>()
+                .unwrap()
+                .to_string(),
+            "unicode+lowercase+stopwords+stem"
+        );
+        assert!("whitespace+upcase".parse::<Analyzer>().is_err());
+        assert!("".parse::<Analyzer>().is_err());
+    }
+}
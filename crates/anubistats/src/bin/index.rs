@@ -2,44 +2,123 @@
 //!
 //! 1. The inverted index for words in the Hacker News titles.
 //!    The file maps words to the offset of the postings list for that word in the postings lists file.
-//! 2. The postings list for each word in the Hacker News titles.
+//! 2. The postings list for each word in the Hacker News titles, along with the positions of the word in each title
+//!    and the number of titles containing the word.
+//!    The other searchable fields like the URLs and the authors have their own postings lists files.
 //! 3. The columnar store for the Hacker News entries to show the info of each entry.
+//!    Its schema metadata records the analyzer that produced the words in the postings lists.
+//!    The registrable domains of the URLs are stored in a dictionary-encoded column for aggregations,
+//!    in addition to their postings lists for the queries like `domain:nytimes.com`.
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    sync::Arc,
+};
 
-use std::{collections::BTreeMap, fs::File, sync::Arc};
-
-use anubistats::read_datasets;
+use anubistats::{
+    analysis::{Analyzer, ANALYZER_METADATA_KEY},
+    field::Field as IndexedField,
+    postings::PostingsListBuilder,
+    read_datasets, DATE_FORMAT,
+};
 use arrow::{
-    array::{BinaryBuilder, Int64Builder, StringBuilder, UInt32Builder, UInt64Builder},
-    datatypes::{DataType, Field, Schema},
+    array::{
+        BinaryBuilder, Int64Builder, StringBuilder, StringDictionaryBuilder, UInt32Builder,
+        UInt64Builder,
+    },
+    datatypes::{DataType, Field, Int32Type, Schema},
     record_batch::RecordBatch,
 };
 use parquet::arrow::ArrowWriter;
-use roaring::RoaringBitmap;
-use time::{format_description::FormatItem, OffsetDateTime};
+use time::OffsetDateTime;
+
+fn write_postings_lists(
+    path: &str,
+    postings_lists: BTreeMap<String, PostingsListBuilder>,
+) -> anyhow::Result<()> {
+    let mut word_builder = StringBuilder::new();
+    let mut postings_list_builder = BinaryBuilder::new();
+    let mut positions_builder = BinaryBuilder::new();
+    let mut document_frequency_builder = UInt64Builder::new();
+
+    for (word, postings_list) in postings_lists {
+        let postings_list = postings_list.finish();
+        let mut buffer = Vec::with_capacity(postings_list.documents.serialized_size());
+        postings_list.documents.serialize_into(&mut buffer)?;
 
-const DATE_FORMAT: &[FormatItem<'_>] = time::macros::format_description!("[year][month][day]");
+        word_builder.append_value(word);
+        postings_list_builder.append_value(buffer);
+        positions_builder.append_value(postings_list.positions);
+        document_frequency_builder.append_value(postings_list.documents.len());
+    }
+
+    let word_offset_schema = Schema::new(vec![
+        Field::new("word", DataType::Utf8, false),
+        Field::new("postings_list", DataType::Binary, false),
+        Field::new("positions", DataType::Binary, false),
+        Field::new("document_frequency", DataType::UInt64, false),
+    ]);
+    let word_batch = RecordBatch::try_new(
+        Arc::new(word_offset_schema),
+        vec![
+            Arc::new(word_builder.finish()),
+            Arc::new(postings_list_builder.finish()),
+            Arc::new(positions_builder.finish()),
+            Arc::new(document_frequency_builder.finish()),
+        ],
+    )?;
+
+    let postings_lists_file = File::create(path)?;
+    let mut writer = ArrowWriter::try_new(postings_lists_file, word_batch.schema(), None)?;
+    writer.write(&word_batch)?;
+    writer.close()?;
+
+    Ok(())
+}
 
 fn main() -> anyhow::Result<()> {
-    // Construct postings lists from the words in the titles.
-    let mut postings_lists = BTreeMap::new();
+    let mut analyzer = Analyzer::default();
+    for arg in std::env::args().skip(1) {
+        if let Some(value) = arg.strip_prefix("--analyzer=") {
+            analyzer = value.parse()?;
+        } else {
+            anyhow::bail!("unknown argument: {arg}");
+        }
+    }
+
+    // Construct postings lists from the words in each field.
+    let mut postings_lists: BTreeMap<IndexedField, BTreeMap<String, PostingsListBuilder>> =
+        BTreeMap::new();
     let mut id_builder = UInt32Builder::new();
     let mut doc_id_builder = UInt64Builder::new();
     let mut title_builder = StringBuilder::new();
     let mut date_builder = StringBuilder::new();
     let mut score_builder = UInt64Builder::new();
     let mut descendants_builder = Int64Builder::new();
+    // Many stories share a few domains, so they are stored in a dictionary.
+    let mut domain_builder = StringDictionaryBuilder::<Int32Type>::new();
 
     for (roaring_id, record) in (read_datasets()?).enumerate() {
         let record = record?;
+        let domain = record.domain();
 
         // Add to postings lists
-        for word in record.title.split_whitespace() {
-            let word = word.to_lowercase();
-            if !word.is_empty() {
-                let postings_list = postings_lists
-                    .entry(word)
-                    .or_insert_with(RoaringBitmap::new);
-                postings_list.push(roaring_id.try_into()?);
+        for field in IndexedField::ALL {
+            // The exact fields would be the same as their base fields without stemming.
+            if field.is_exact() && !analyzer.stems() {
+                continue;
+            }
+            let field_postings_lists = postings_lists.entry(field).or_default();
+            let value = match field {
+                // Reuse the domain for the stored fields instead of extracting it again.
+                IndexedField::Domain => Cow::Borrowed(domain.as_deref().unwrap_or_default()),
+                field => record.field(field),
+            };
+            for (position, word) in analyzer.analyze_for_index(field, &value) {
+                let postings_list = field_postings_lists.entry(word).or_default();
+                postings_list.push(roaring_id.try_into()?, position.try_into()?);
             }
         }
 
@@ -59,6 +138,10 @@ fn main() -> anyhow::Result<()> {
 
         score_builder.append_option(record.score);
         descendants_builder.append_option(record.descendants);
+        match domain {
+            Some(domain) => domain_builder.append_value(domain),
+            None => domain_builder.append_null(),
+        }
     }
 
     let schema = Schema::new(vec![
@@ -68,7 +151,16 @@ fn main() -> anyhow::Result<()> {
         Field::new("date", DataType::Utf8, true),
         Field::new("score", DataType::UInt64, true),
         Field::new("descendants", DataType::Int64, true),
-    ]);
+        Field::new(
+            "domain",
+            DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8)),
+            true,
+        ),
+    ])
+    .with_metadata(HashMap::from([(
+        ANALYZER_METADATA_KEY.to_string(),
+        analyzer.to_string(),
+    )]));
     let batch = RecordBatch::try_new(
         Arc::new(schema),
         vec![
@@ -78,41 +170,18 @@ fn main() -> anyhow::Result<()> {
             Arc::new(date_builder.finish()),
             Arc::new(score_builder.finish()),
             Arc::new(descendants_builder.finish()),
+            Arc::new(domain_builder.finish()),
         ],
     )?;
 
-    let mut word_builder = StringBuilder::new();
-    let mut postings_list_builder = BinaryBuilder::new();
-
-    for (word, postings_list) in postings_lists {
-        let mut buffer = Vec::with_capacity(postings_list.serialized_size());
-        postings_list.serialize_into(&mut buffer)?;
-
-        word_builder.append_value(word);
-        postings_list_builder.append_value(buffer);
-    }
-
     let stored_fields_file = File::create("stored_fields.parquet")?;
     let mut writer = ArrowWriter::try_new(stored_fields_file, batch.schema(), None)?;
     writer.write(&batch)?;
     writer.close()?;
 
-    let word_offset_schema = Schema::new(vec![
-        Field::new("word", DataType::Utf8, false),
-        Field::new("postings_list", DataType::Binary, false),
-    ]);
-    let word_batch = RecordBatch::try_new(
-        Arc::new(word_offset_schema),
-        vec![
-            Arc::new(word_builder.finish()),
-            Arc::new(postings_list_builder.finish()),
-        ],
-    )?;
-
-    let postings_lists_file = File::create("postings_lists.parquet")?;
-    let mut writer = ArrowWriter::try_new(postings_lists_file, word_batch.schema(), None)?;
-    writer.write(&word_batch)?;
-    writer.close()?;
+    for (field, postings_lists) in postings_lists {
+        write_postings_lists(field.postings_lists_path(), postings_lists)?;
+    }
 
     Ok(())
 }
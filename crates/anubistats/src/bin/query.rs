@@ -2,18 +2,31 @@
 
 use std::{
     collections::{hash_map::Entry, HashMap},
+    fmt,
     fs::File,
     io::BufRead,
+    ops::{Bound, RangeBounds},
     sync::Arc,
 };
 
-use anubistats_query::Query;
+use anubistats::{
+    analysis::{Analyzer, ANALYZER_METADATA_KEY},
+    field::{registrable_domain, Field},
+    fuzzy::FuzzyMatcher,
+    pattern::RegexMatcher,
+    postings::PostingsList,
+    DATE_FORMAT,
+};
+use anubistats_query::{
+    normalize::NormalizedQuery, DefaultOperator, ParseOptions, Precedence, Query, RangeBound,
+};
 use arrow::{
     array::{
         Array, ArrayBuilder, AsArray, BinaryArray, BooleanArray, StringArray, StringBuilder,
         UInt32Array, UInt64Array, UInt64Builder,
     },
-    datatypes::DataType,
+    datatypes::{DataType, Int32Type, UInt64Type},
+    record_batch::RecordBatch,
     row::{RowConverter, SortField},
 };
 use parquet::{
@@ -26,11 +39,21 @@ use parquet::{
     },
     file::page_index::index::Index,
 };
-use roaring::RoaringBitmap;
+use roaring::{MultiOps, RoaringBitmap};
+use time::{format_description::FormatItem, Date};
 
-fn find_postings_list_parquet(word: &str) -> anyhow::Result<RoaringBitmap> {
+/// The format of the dates in range predicates like `date:2020-01-01..2021-12-31`.
+const QUERY_DATE_FORMAT: &[FormatItem<'_>] =
+    time::macros::format_description!("[year]-[month]-[day]");
+
+/// Reads the row for `word` from the postings lists file at `path`, projected to the given columns.
+fn find_postings_list_row_parquet(
+    path: &str,
+    word: &str,
+    columns: &[&str],
+) -> anyhow::Result<Option<RecordBatch>> {
     let word = word.to_string();
-    let file = File::open("postings_lists.parquet")?;
+    let file = File::open(path)?;
     let builder = ParquetRecordBatchReaderBuilder::try_new_with_options(
         file,
         ArrowReaderOptions::new().with_page_index(true),
@@ -113,46 +136,1217 @@ fn find_postings_list_parquet(word: &str) -> anyhow::Result<RoaringBitmap> {
         },
     );
     let row_filter = RowFilter::new(vec![Box::new(predicate)]);
+    let projection = ProjectionMask::leaves(
+        builder.parquet_schema(),
+        builder
+            .parquet_schema()
+            .columns()
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| columns.contains(&c.name()))
+            .map(|(i, _)| i),
+    );
     let mut reader = builder
         .with_row_selection(RowSelection::from(selectors))
         .with_row_filter(row_filter)
+        .with_projection(projection)
         .build()?;
 
     if let Some(batch) = reader.next() {
         let batch = batch?;
         if batch.num_rows() > 0 {
-            let postings_lists: &BinaryArray = batch["postings_list"].as_binary();
-            let postings_list_bytes = postings_lists.value(0);
-            let postings_list = RoaringBitmap::deserialize_from(postings_list_bytes)?;
-
-            Ok(postings_list)
+            Ok(Some(batch))
         } else {
-            Ok(RoaringBitmap::new())
+            Ok(None)
         }
     } else {
-        Ok(RoaringBitmap::new())
+        Ok(None)
+    }
+}
+
+fn find_postings_list_parquet(path: &str, word: &str) -> anyhow::Result<RoaringBitmap> {
+    match find_postings_list_row_parquet(path, word, &["postings_list"])? {
+        Some(batch) => {
+            let postings_lists: &BinaryArray = batch["postings_list"].as_binary();
+            Ok(RoaringBitmap::deserialize_from(postings_lists.value(0))?)
+        }
+        None => Ok(RoaringBitmap::new()),
+    }
+}
+
+/// Returns the number of documents containing `word`, reading the document frequency column
+/// instead of the postings list.
+fn find_document_frequency_parquet(path: &str, word: &str) -> anyhow::Result<u64> {
+    // The word is projected too, so that the row is found even if the index predates the
+    // frequencies and has no column to project otherwise.
+    match find_postings_list_row_parquet(path, word, &["word", "document_frequency"])? {
+        Some(batch) => {
+            let document_frequencies: &UInt64Array = batch
+                .column_by_name("document_frequency")
+                .ok_or_else(|| {
+                    anyhow::anyhow!("{path} has no document frequencies; rebuild the index")
+                })?
+                .as_primitive();
+            Ok(document_frequencies.value(0))
+        }
+        None => Ok(0),
     }
 }
 
-fn eval_query<F>(query: &Query, find_postings_list: &F) -> anyhow::Result<RoaringBitmap>
+fn find_positional_postings_list_parquet(path: &str, word: &str) -> anyhow::Result<PostingsList> {
+    match find_postings_list_row_parquet(path, word, &["postings_list", "positions"])? {
+        Some(batch) => {
+            let postings_lists: &BinaryArray = batch["postings_list"].as_binary();
+            let positions: &BinaryArray = batch
+                .column_by_name("positions")
+                .ok_or_else(|| anyhow::anyhow!("{path} has no positions; rebuild the index"))?
+                .as_binary();
+            Ok(PostingsList {
+                documents: RoaringBitmap::deserialize_from(postings_lists.value(0))?,
+                positions: positions.value(0).to_vec(),
+            })
+        }
+        None => Ok(PostingsList::default()),
+    }
+}
+
+/// Returns the bitmap of all the documents in the index, against which negations are evaluated.
+fn universe_parquet() -> anyhow::Result<RoaringBitmap> {
+    let file = File::open("stored_fields.parquet")?;
+    let builder = ParquetRecordBatchReaderBuilder::try_new(file)?;
+    let num_rows = builder.metadata().file_metadata().num_rows();
+
+    let mut universe = RoaringBitmap::new();
+    universe.insert_range(0..u32::try_from(num_rows)?);
+    Ok(universe)
+}
+
+/// Returns the name of the analyzer recorded in the index, or `None` if the index predates the record.
+fn recorded_analyzer_parquet() -> anyhow::Result<Option<String>> {
+    let file = File::open("stored_fields.parquet")?;
+    let builder = ParquetRecordBatchReaderBuilder::try_new(file)?;
+    Ok(builder
+        .schema()
+        .metadata()
+        .get(ANALYZER_METADATA_KEY)
+        .cloned())
+}
+
+/// Selects the pages of `column` for which `select` returns true.
+///
+/// `select` is called with the column index of a row group and the index of a page in it.
+fn select_pages<F>(
+    builder: &ParquetRecordBatchReaderBuilder<File>,
+    column: &str,
+    select: F,
+) -> RowSelection
+where
+    F: Fn(&Index, usize) -> bool,
+{
+    let metadata = builder.metadata();
+    let offset_indexes = metadata.offset_indexes().unwrap();
+    let page_indexes = metadata.page_indexes().unwrap();
+    let column_index = builder
+        .parquet_schema()
+        .columns()
+        .iter()
+        .position(|c| c.name() == column)
+        .unwrap();
+
+    let mut selectors = vec![];
+    for row_group in 0..offset_indexes.len() {
+        let offset_index = &offset_indexes[row_group][column_index];
+        let page_index = &page_indexes[row_group][column_index];
+        let row_group_end = metadata.row_group(row_group).num_rows();
+
+        for (idx, page_location) in offset_index.iter().enumerate() {
+            let page_start = page_location.first_row_index;
+            let page_end = if idx + 1 < offset_index.len() {
+                offset_index[idx + 1].first_row_index
+            } else {
+                row_group_end
+            };
+            let page_len = (page_end - page_start).try_into().unwrap();
+
+            if select(page_index, idx) {
+                selectors.push(RowSelector::select(page_len));
+            } else {
+                selectors.push(RowSelector::skip(page_len));
+            }
+        }
+    }
+
+    RowSelection::from(selectors)
+}
+
+/// Selects the pages of the word column that may contain words starting with `prefix`.
+fn select_prefix_pages(
+    builder: &ParquetRecordBatchReaderBuilder<File>,
+    prefix: &str,
+) -> RowSelection {
+    // Words starting with `prefix` are in [prefix, prefix + 0xFF) since 0xFF never appears in UTF-8.
+    let lower = prefix.as_bytes();
+    let upper = [lower, &[0xff]].concat();
+
+    // ASSUMPTION:
+    // 1. The index is byte array index
+    // 2. The index is sorted in ascending order
+    select_pages(builder, "word", |page_index, idx| match page_index {
+        Index::BYTE_ARRAY(index) => {
+            let min = index.indexes[idx].min.as_ref().unwrap().data();
+            let max = index.indexes[idx].max.as_ref().unwrap().data();
+            max >= lower && min < upper.as_slice()
+        }
+        _ => unreachable!(),
+    })
+}
+
+/// Returns the postings lists of the words starting with `prefix` that `filter` accepts in the
+/// postings lists file at `path`.
+///
+/// `filter` is called with the words in ascending order.
+/// Fails if more than `max_expansions` words are accepted.
+fn expand_prefix_parquet<F>(
+    path: &str,
+    prefix: &str,
+    mut filter: F,
+    max_expansions: usize,
+) -> anyhow::Result<Vec<RoaringBitmap>>
 where
-    F: Fn(&str) -> anyhow::Result<RoaringBitmap>,
+    F: FnMut(&str) -> bool,
 {
+    let mut words = vec![];
+    let selection = scan_words_parquet(path, prefix, |word| {
+        if filter(word) {
+            anyhow::ensure!(
+                words.len() < max_expansions,
+                "the term expands to more than {max_expansions} words"
+            );
+            words.push(word.to_string());
+        }
+        Ok(())
+    })?;
+    read_postings_lists_parquet(path, selection, words)
+}
+
+/// Returns the postings lists of the words within `max_distance` from `word` in the postings lists
+/// file at `path`.
+///
+/// Short words are within the distance from many words, so only the `max_expansions` words closest
+/// to `word` are kept instead of failing.
+fn expand_fuzzy_parquet(
+    path: &str,
+    word: &str,
+    max_distance: u32,
+    max_expansions: usize,
+) -> anyhow::Result<Vec<RoaringBitmap>> {
+    // Fuzzy terms can differ from the word at any position, so we need to scan the entire dictionary.
+    let mut matcher = FuzzyMatcher::new(word, max_distance);
+    let mut words = vec![];
+    let selection = scan_words_parquet(path, "", |candidate| {
+        if let Some(distance) = matcher.distance(candidate) {
+            words.push((distance, candidate.to_string()));
+        }
+        Ok(())
+    })?;
+
+    words.sort();
+    words.truncate(max_expansions);
+    let mut words: Vec<_> = words.into_iter().map(|(_, word)| word).collect();
+    words.sort();
+    read_postings_lists_parquet(path, selection, words)
+}
+
+/// Calls `visit` with the words starting with `prefix` in ascending order in the postings lists
+/// file at `path`, reading only the word column.
+///
+/// Returns the selection of the pages that may contain the words.
+fn scan_words_parquet<F>(path: &str, prefix: &str, mut visit: F) -> anyhow::Result<RowSelection>
+where
+    F: FnMut(&str) -> anyhow::Result<()>,
+{
+    let file = File::open(path)?;
+    let builder = ParquetRecordBatchReaderBuilder::try_new_with_options(
+        file,
+        ArrowReaderOptions::new().with_page_index(true),
+    )?;
+    let selection = select_prefix_pages(&builder, prefix);
+    let projection = ProjectionMask::leaves(
+        builder.parquet_schema(),
+        std::iter::once(
+            builder
+                .parquet_schema()
+                .columns()
+                .iter()
+                .position(|c| c.name() == "word")
+                .unwrap(),
+        ),
+    );
+    let reader = builder
+        .with_row_selection(selection.clone())
+        .with_projection(projection)
+        .build()?;
+
+    for batch in reader {
+        let batch = batch?;
+        let batch_words: &StringArray = batch["word"].as_string();
+        for i in 0..batch.num_rows() {
+            let word = batch_words.value(i);
+            if word.starts_with(prefix) {
+                visit(word)?;
+            }
+        }
+    }
+    Ok(selection)
+}
+
+/// Returns the postings lists of the sorted `words` in the postings lists file at `path`, reading
+/// only the pages in `selection`.
+fn read_postings_lists_parquet(
+    path: &str,
+    selection: RowSelection,
+    words: Vec<String>,
+) -> anyhow::Result<Vec<RoaringBitmap>> {
+    if words.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let file = File::open(path)?;
+    let builder = ParquetRecordBatchReaderBuilder::try_new(file)?;
+    let predicate = ArrowPredicateFn::new(
+        ProjectionMask::leaves(
+            builder.parquet_schema(),
+            std::iter::once(
+                builder
+                    .parquet_schema()
+                    .columns()
+                    .iter()
+                    .position(|c| c.name() == "word")
+                    .unwrap(),
+            ),
+        ),
+        move |batch| {
+            let batch_words: &StringArray = batch.column(0).as_string();
+            Ok((0..batch.num_rows())
+                .map(|i| {
+                    Some(
+                        words
+                            .binary_search_by(|word| word.as_str().cmp(batch_words.value(i)))
+                            .is_ok(),
+                    )
+                })
+                .collect::<BooleanArray>())
+        },
+    );
+    let row_filter = RowFilter::new(vec![Box::new(predicate)]);
+    let projection = ProjectionMask::leaves(
+        builder.parquet_schema(),
+        std::iter::once(
+            builder
+                .parquet_schema()
+                .columns()
+                .iter()
+                .position(|c| c.name() == "postings_list")
+                .unwrap(),
+        ),
+    );
+    let reader = builder
+        .with_row_selection(selection)
+        .with_row_filter(row_filter)
+        .with_projection(projection)
+        .build()?;
+
+    let mut postings_lists = vec![];
+    for batch in reader {
+        let batch = batch?;
+        let batch_postings_lists: &BinaryArray = batch["postings_list"].as_binary();
+        for i in 0..batch.num_rows() {
+            postings_lists.push(RoaringBitmap::deserialize_from(
+                batch_postings_lists.value(i),
+            )?);
+        }
+    }
+    Ok(postings_lists)
+}
+
+/// Returns true if `[min, max]` intersects with `range`.
+fn overlaps<T: PartialOrd>(range: &(Bound<T>, Bound<T>), min: &T, max: &T) -> bool {
+    let above_lower = match &range.0 {
+        Bound::Included(lower) => max >= lower,
+        Bound::Excluded(lower) => max > lower,
+        Bound::Unbounded => true,
+    };
+    let below_upper = match &range.1 {
+        Bound::Included(upper) => min <= upper,
+        Bound::Excluded(upper) => min < upper,
+        Bound::Unbounded => true,
+    };
+    above_lower && below_upper
+}
+
+/// A range predicate on a column of the stored fields.
+enum StoredFieldRange {
+    Score((Bound<u64>, Bound<u64>)),
+    /// The dates are formatted in `DATE_FORMAT` like in the stored fields.
+    Date((Bound<String>, Bound<String>)),
+}
+
+impl StoredFieldRange {
+    /// The names of the stored fields supporting range predicates.
+    const FIELDS: [&'static str; 2] = ["score", "date"];
+
+    /// Interprets the range predicate `name:lower..upper` in a query.
+    fn new(name: &str, lower: &RangeBound, upper: &RangeBound) -> anyhow::Result<Self> {
+        fn convert<T>(
+            bound: &RangeBound,
+            parse: impl Fn(&str) -> anyhow::Result<T>,
+        ) -> anyhow::Result<Bound<T>> {
+            Ok(match bound {
+                RangeBound::Included(value) => Bound::Included(parse(value)?),
+                RangeBound::Excluded(value) => Bound::Excluded(parse(value)?),
+                RangeBound::Unbounded => Bound::Unbounded,
+            })
+        }
+
+        match name {
+            "score" => {
+                let parse = |value: &str| -> anyhow::Result<u64> {
+                    value
+                        .parse()
+                        .map_err(|_| anyhow::anyhow!("invalid score: {value}"))
+                };
+                Ok(Self::Score((
+                    convert(lower, parse)?,
+                    convert(upper, parse)?,
+                )))
+            }
+            "date" => {
+                let parse = |value: &str| -> anyhow::Result<String> {
+                    let date = Date::parse(value, QUERY_DATE_FORMAT).map_err(|_| {
+                        anyhow::anyhow!("invalid date: {value} (expected YYYY-MM-DD)")
+                    })?;
+                    Ok(date.format(DATE_FORMAT)?)
+                };
+                Ok(Self::Date((convert(lower, parse)?, convert(upper, parse)?)))
+            }
+            _ => anyhow::bail!("field {name} does not support ranges"),
+        }
+    }
+}
+
+/// Returns the documents whose value of `column` in the stored fields is within `range`.
+///
+/// `page_min_max` returns the minimum and maximum values of a page from the column index, which
+/// are used to skip the pages entirely out of `range`. `value` extracts a value from the column.
+fn find_range_parquet<T, P, V>(
+    column: &str,
+    range: (Bound<T>, Bound<T>),
+    page_min_max: P,
+    value: V,
+) -> anyhow::Result<RoaringBitmap>
+where
+    T: PartialOrd,
+    P: Fn(&Index, usize) -> Option<(T, T)>,
+    V: Fn(&dyn Array, usize) -> T,
+{
+    let file = File::open("stored_fields.parquet")?;
+    let builder = ParquetRecordBatchReaderBuilder::try_new_with_options(
+        file,
+        ArrowReaderOptions::new().with_page_index(true),
+    )?;
+
+    // Pages without statistics only have null values, which never match the range.
+    let selection = select_pages(&builder, column, |page_index, idx| {
+        match page_min_max(page_index, idx) {
+            Some((min, max)) => overlaps(&range, &min, &max),
+            None => matches!(page_index, Index::NONE),
+        }
+    });
+    let projection = ProjectionMask::leaves(
+        builder.parquet_schema(),
+        builder
+            .parquet_schema()
+            .columns()
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.name() == "id" || c.name() == column)
+            .map(|(i, _)| i),
+    );
+    let reader = builder
+        .with_row_selection(selection)
+        .with_projection(projection)
+        .build()?;
+
+    let mut documents = RoaringBitmap::new();
+    for batch in reader {
+        let batch = batch?;
+        let roaring_ids: &UInt32Array = batch["id"].as_primitive();
+        let values = batch[column].as_ref();
+        for i in 0..batch.num_rows() {
+            if !values.is_null(i) && range.contains(&value(values, i)) {
+                documents.insert(roaring_ids.value(i));
+            }
+        }
+    }
+    Ok(documents)
+}
+
+fn find_stored_field_range_parquet(range: &StoredFieldRange) -> anyhow::Result<RoaringBitmap> {
+    match range {
+        StoredFieldRange::Score(range) => find_range_parquet(
+            "score",
+            *range,
+            |page_index, idx| match page_index {
+                Index::INT64(index) => {
+                    let page_index = &index.indexes[idx];
+                    Some((page_index.min? as u64, page_index.max? as u64))
+                }
+                _ => None,
+            },
+            |values, i| values.as_primitive::<UInt64Type>().value(i),
+        ),
+        StoredFieldRange::Date(range) => find_range_parquet(
+            "date",
+            range.clone(),
+            |page_index, idx| match page_index {
+                Index::BYTE_ARRAY(index) => {
+                    let page_index = &index.indexes[idx];
+                    Some((
+                        String::from_utf8_lossy(page_index.min.as_ref()?.data()).into_owned(),
+                        String::from_utf8_lossy(page_index.max.as_ref()?.data()).into_owned(),
+                    ))
+                }
+                _ => None,
+            },
+            |values, i| values.as_string::<i32>().value(i).to_string(),
+        ),
+    }
+}
+
+/// The index that queries are evaluated against.
+trait InvertedIndex {
+    /// Returns all the documents in the index, against which negations are evaluated.
+    fn universe(&self) -> &RoaringBitmap;
+
+    /// Returns the analyzer that the words in queries are analyzed with, which matches the one
+    /// that built the index.
+    fn analyzer(&self) -> &Analyzer;
+
+    /// Returns the documents containing `word` in `field`.
+    fn postings_list(&self, field: Field, word: &str) -> anyhow::Result<RoaringBitmap>;
+
+    /// Returns the number of documents containing `word` in `field` without reading its postings
+    /// list.
+    fn document_frequency(&self, field: Field, word: &str) -> anyhow::Result<u64>;
+
+    /// Returns the documents containing `word` in `field` along with the positions of `word` in them.
+    fn positional_postings_list(&self, field: Field, word: &str) -> anyhow::Result<PostingsList>;
+
+    /// Returns the postings lists of the words in `field` starting with `prefix` that `filter` accepts.
+    ///
+    /// `filter` is called with the words in ascending order.
+    fn expand_prefix<F>(
+        &self,
+        field: Field,
+        prefix: &str,
+        filter: F,
+    ) -> anyhow::Result<Vec<RoaringBitmap>>
+    where
+        F: FnMut(&str) -> bool;
+
+    /// Returns the postings lists of the words in `field` within `max_distance` from `word`.
+    fn expand_fuzzy(
+        &self,
+        field: Field,
+        word: &str,
+        max_distance: u32,
+    ) -> anyhow::Result<Vec<RoaringBitmap>>;
+
+    /// Returns the documents whose stored field is within the range.
+    fn stored_field_range(&self, range: &StoredFieldRange) -> anyhow::Result<RoaringBitmap>;
+}
+
+/// The default maximum number of words a single term in a query can expand to.
+///
+/// Prefix and regular expression terms expanding to more words fail, while fuzzy terms keep the
+/// closest words.
+const DEFAULT_MAX_EXPANSIONS: usize = 1024;
+
+struct ParquetIndex {
+    universe: RoaringBitmap,
+    analyzer: Analyzer,
+    max_expansions: usize,
+}
+
+impl ParquetIndex {
+    /// Opens the index, refusing it if it was built with an analyzer other than `analyzer`.
+    fn open(analyzer: Analyzer, max_expansions: usize) -> anyhow::Result<Self> {
+        match recorded_analyzer_parquet()? {
+            Some(recorded) => anyhow::ensure!(
+                recorded == analyzer.to_string(),
+                "the index was built with the analyzer {recorded}, but the queries are analyzed \
+                 with {analyzer}; pass --analyzer={recorded} or rebuild the index"
+            ),
+            None => eprintln!(
+                "warning: the index does not record its analyzer, so the queries are analyzed \
+                 with {analyzer} assuming that the index was built with it"
+            ),
+        }
+
+        Ok(Self {
+            universe: universe_parquet()?,
+            analyzer,
+            max_expansions,
+        })
+    }
+}
+
+impl InvertedIndex for ParquetIndex {
+    fn universe(&self) -> &RoaringBitmap {
+        &self.universe
+    }
+
+    fn analyzer(&self) -> &Analyzer {
+        &self.analyzer
+    }
+
+    fn postings_list(&self, field: Field, word: &str) -> anyhow::Result<RoaringBitmap> {
+        find_postings_list_parquet(field.postings_lists_path(), word)
+    }
+
+    fn document_frequency(&self, field: Field, word: &str) -> anyhow::Result<u64> {
+        find_document_frequency_parquet(field.postings_lists_path(), word)
+    }
+
+    fn positional_postings_list(&self, field: Field, word: &str) -> anyhow::Result<PostingsList> {
+        find_positional_postings_list_parquet(field.postings_lists_path(), word)
+    }
+
+    fn expand_prefix<F>(
+        &self,
+        field: Field,
+        prefix: &str,
+        filter: F,
+    ) -> anyhow::Result<Vec<RoaringBitmap>>
+    where
+        F: FnMut(&str) -> bool,
+    {
+        expand_prefix_parquet(
+            field.postings_lists_path(),
+            prefix,
+            filter,
+            self.max_expansions,
+        )
+    }
+
+    fn expand_fuzzy(
+        &self,
+        field: Field,
+        word: &str,
+        max_distance: u32,
+    ) -> anyhow::Result<Vec<RoaringBitmap>> {
+        expand_fuzzy_parquet(
+            field.postings_lists_path(),
+            word,
+            max_distance,
+            self.max_expansions,
+        )
+    }
+
+    fn stored_field_range(&self, range: &StoredFieldRange) -> anyhow::Result<RoaringBitmap> {
+        find_stored_field_range_parquet(range)
+    }
+}
+
+/// Returns the documents containing all of `words` in `field`, along with the positions of each
+/// word in them.
+///
+/// `positions[i][j]` is the positions of the `i`-th word in the `j`-th document.
+fn find_positions<I: InvertedIndex>(
+    field: Field,
+    words: &[&str],
+    index: &I,
+) -> anyhow::Result<(RoaringBitmap, Vec<Vec<Vec<u32>>>)> {
+    let postings_lists = words
+        .iter()
+        .map(|word| index.positional_postings_list(field, word))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let Some((first, rest)) = postings_lists.split_first() else {
+        return Ok((RoaringBitmap::new(), vec![]));
+    };
+    let mut candidates = first.documents.clone();
+    for postings_list in rest {
+        candidates &= &postings_list.documents;
+    }
+
+    let positions = postings_lists
+        .iter()
+        .map(|postings_list| postings_list.positions_in(&candidates))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    Ok((candidates, positions))
+}
+
+/// Returns the documents where `words` appear adjacent to each other in this order in `field`.
+///
+/// The gaps in `words` match any word.
+fn eval_phrase<I: InvertedIndex>(
+    field: Field,
+    words: &[Option<String>],
+    index: &I,
+) -> anyhow::Result<RoaringBitmap> {
+    let (offsets, words): (Vec<u32>, Vec<&str>) = (0..)
+        .zip(words)
+        .filter_map(|(offset, word)| Some((offset, word.as_deref()?)))
+        .unzip();
+    let (candidates, positions) = find_positions(field, &words, index)?;
+    if positions.is_empty() {
+        return Ok(candidates);
+    }
+
+    Ok(candidates
+        .iter()
+        .enumerate()
+        .filter(|&(j, _)| {
+            positions[0][j].iter().any(|&start| {
+                positions
+                    .iter()
+                    .zip(offsets.iter())
+                    .skip(1)
+                    .all(|(positions, &offset)| {
+                        positions[j]
+                            .binary_search(&(start + offset - offsets[0]))
+                            .is_ok()
+                    })
+            })
+        })
+        .map(|(_, document)| document)
+        .collect())
+}
+
+/// Returns the documents where `lhs` and `rhs` appear within `distance` words of each other in `field`.
+fn eval_near<I: InvertedIndex>(
+    field: Field,
+    lhs: &str,
+    rhs: &str,
+    distance: u32,
+    index: &I,
+) -> anyhow::Result<RoaringBitmap> {
+    let (candidates, positions) = find_positions(field, &[lhs, rhs], index)?;
+
+    Ok(candidates
+        .iter()
+        .enumerate()
+        .filter(|&(j, _)| {
+            // Both positions are sorted, so we can find the closest pair by merging them.
+            let (lhs, rhs) = (&positions[0][j], &positions[1][j]);
+            let (mut l, mut r) = (0, 0);
+            while l < lhs.len() && r < rhs.len() {
+                if lhs[l].abs_diff(rhs[r]) <= distance {
+                    return true;
+                }
+                if lhs[l] < rhs[r] {
+                    l += 1;
+                } else {
+                    r += 1;
+                }
+            }
+            false
+        })
+        .map(|(_, document)| document)
+        .collect())
+}
+
+/// Analyzes the words in `query` as the indexer analyzed the values of the fields, searching the
+/// terms without field names in `field`.
+///
+/// A word analyzed into several terms, like a hyphenated word or the CJK bigrams of `東京都`, is
+/// searched as a phrase. If the analyzer stems the words, the terms matched against the words as
+/// written, like `=running` or `run*`, are searched in the exact field instead.
+///
+/// The words removed from a phrase, like stopwords, are left as gaps matching any word, so that
+/// `"state of the art"` becomes `"state ? ? art"` and matches the words `state` and `art` three
+/// words apart as the indexer positioned them. The last character of a CJK run starts no bigram,
+/// so its position is left as a gap too, like in `"東京 京都 ? rust"` for `"東京都 rust"`.
+///
+/// Words without terms to search for, like stopwords, are not indexed, so they are dropped from the
+/// query and pushed to `ignored` instead of making conjunctions empty. An operand of `NEAR` dropped
+/// this way leaves the other operand alone. Returns `None` if the whole query is dropped.
+fn analyze_query(
+    query: &Query,
+    field: Field,
+    analyzer: &Analyzer,
+    ignored: &mut Vec<String>,
+) -> anyhow::Result<Option<Query>> {
+    let mut terms = |terms: Vec<(usize, String)>, original: &Query| {
+        let (Some(&(first, _)), Some(&(last, _))) = (terms.first(), terms.last()) else {
+            ignored.push(original.to_string());
+            return None;
+        };
+        if first == last {
+            return terms.into_iter().next().map(|(_, term)| Query::Word(term));
+        }
+        // The positions of the words removed by the analyzer are left as gaps in the phrase.
+        let mut words = vec![None; last - first + 1];
+        for (position, term) in terms {
+            words[position - first] = Some(term);
+        }
+        Some(Query::Phrase(words))
+    };
+    let exact_field = if analyzer.stems() {
+        field.exact()
+    } else {
+        field
+    };
+    let in_exact_field = |query: Query| {
+        if exact_field == field {
+            query
+        } else {
+            Query::Field(exact_field.name().to_string(), Box::new(query))
+        }
+    };
+    // Drops the missing operands of a binary operator.
+    let combine = |op: fn(Box<Query>, Box<Query>) -> Query,
+                   lhs: Option<Query>,
+                   rhs: Option<Query>| match (lhs, rhs) {
+        (Some(lhs), Some(rhs)) => Some(op(Box::new(lhs), Box::new(rhs))),
+        (lhs, rhs) => lhs.or(rhs),
+    };
+
+    Ok(match query {
+        // The indexer reduces the URLs to their registrable domains, so the values in queries like
+        // `domain:www.nytimes.com` are reduced as well.
+        Query::Word(value) | Query::Exact(value) if field == Field::Domain => {
+            let domain = registrable_domain(value)
+                .ok_or_else(|| anyhow::anyhow!("{value} has no registrable domain"))?;
+            terms(analyzer.analyze_with_positions(field, &domain), query)
+        }
+        Query::Word(word) => terms(analyzer.analyze_with_positions(field, word), query),
+        Query::Exact(word) => {
+            terms(analyzer.analyze_with_positions(exact_field, word), query).map(in_exact_field)
+        }
+        Query::Phrase(words) => {
+            // The runs of words between the gaps are analyzed together, and each gap takes a
+            // position as a removed word does.
+            let mut positioned = vec![];
+            let mut offset = 0;
+            for (i, run) in words.split(Option::is_none).enumerate() {
+                if i > 0 {
+                    offset += 1;
+                }
+                let run: Vec<_> = run.iter().flatten().map(String::as_str).collect();
+                let run = run.join(" ");
+                positioned.extend(
+                    analyzer
+                        .analyze_with_positions(field, &run)
+                        .into_iter()
+                        .map(|(position, term)| (offset + position, term)),
+                );
+                offset += analyzer.tokenizer.count_positions(field, &run);
+            }
+            terms(positioned, query)
+        }
+        Query::Near(lhs, rhs, distance) => {
+            let mut analyze = |word: &str| match analyzer.analyze(field, word).as_slice() {
+                [] => {
+                    ignored.push(Query::Word(word.to_string()).to_string());
+                    Ok(None)
+                }
+                [term] => Ok(Some(term.clone())),
+                _ => Err(anyhow::anyhow!(
+                    "the operands of NEAR must be single terms, but {word} is not"
+                )),
+            };
+            match (analyze(lhs)?, analyze(rhs)?) {
+                (Some(lhs), Some(rhs)) => Some(Query::Near(lhs, rhs, *distance)),
+                (lhs, rhs) => lhs.or(rhs).map(Query::Word),
+            }
+        }
+        // Prefixes and fuzzy terms are compared with the words character by character, so they
+        // are filtered without being split or stemmed.
+        Query::Prefix(prefix) => Some(in_exact_field(Query::Prefix(
+            analyzer.filter(prefix.clone()),
+        ))),
+        Query::Fuzzy(word, distance) => Some(in_exact_field(Query::Fuzzy(
+            analyzer.filter(word.clone()),
+            *distance,
+        ))),
+        // Regular expressions are matched as they are.
+        Query::Regex(_) => Some(in_exact_field(query.clone())),
+        Query::Range(_, _, _) => Some(query.clone()),
+        Query::Field(name, query) => match Field::from_name(name) {
+            Some(field) => {
+                // The exact fields are not indexed if the analyzer does not stem.
+                let field = if analyzer.stems() {
+                    field
+                } else {
+                    field.base()
+                };
+                analyze_query(query, field, analyzer, ignored)?
+                    .map(|query| Query::Field(field.name().to_string(), Box::new(query)))
+            }
+            // The fields only supporting ranges and exact values are not analyzed.
+            None => Some(Query::Field(name.clone(), query.clone())),
+        },
+        Query::And(lhs, rhs) => combine(
+            Query::And,
+            analyze_query(lhs, field, analyzer, ignored)?,
+            analyze_query(rhs, field, analyzer, ignored)?,
+        ),
+        Query::Or(lhs, rhs) => combine(
+            Query::Or,
+            analyze_query(lhs, field, analyzer, ignored)?,
+            analyze_query(rhs, field, analyzer, ignored)?,
+        ),
+        Query::Not(query) => {
+            analyze_query(query, field, analyzer, ignored)?.map(|query| Query::Not(Box::new(query)))
+        }
+    })
+}
+
+/// Evaluates a term of a normalized query in `field`.
+fn eval_term<I: InvertedIndex>(
+    query: &Query,
+    field: Field,
+    index: &I,
+) -> anyhow::Result<RoaringBitmap> {
     match query {
-        anubistats_query::Query::Word(word) => Ok(find_postings_list(word)?),
-        anubistats_query::Query::And(lhs, rhs) => {
-            let lhs = eval_query(lhs, find_postings_list)?;
-            let rhs = eval_query(rhs, find_postings_list)?;
-            Ok(lhs & rhs)
+        anubistats_query::Query::Word(word) => Ok(index.postings_list(field, word)?),
+        anubistats_query::Query::Phrase(words) => eval_phrase(field, words, index),
+        anubistats_query::Query::Near(lhs, rhs, distance) => {
+            eval_near(field, lhs, rhs, *distance, index)
+        }
+        anubistats_query::Query::Prefix(prefix) => {
+            Ok(index.expand_prefix(field, prefix, |_| true)?.union())
+        }
+        anubistats_query::Query::Fuzzy(word, distance) => {
+            Ok(index.expand_fuzzy(field, word, *distance)?.union())
+        }
+        anubistats_query::Query::Regex(pattern) => {
+            let matcher = RegexMatcher::new(pattern)?;
+            Ok(index
+                .expand_prefix(field, matcher.prefix(), |word| matcher.matches(word))?
+                .union())
+        }
+        anubistats_query::Query::Range(name, lower, upper) => {
+            index.stored_field_range(&StoredFieldRange::new(name, lower, upper)?)
+        }
+        anubistats_query::Query::Field(name, query)
+            if StoredFieldRange::FIELDS.contains(&name.as_str()) =>
+        {
+            // Words in the fields only supporting ranges are interpreted as exact values.
+            let Query::Word(value) = query.as_ref() else {
+                anyhow::bail!("field {name} only supports ranges and exact values");
+            };
+            let value = RangeBound::Included(value.clone());
+            index.stored_field_range(&StoredFieldRange::new(name, &value, &value)?)
+        }
+        anubistats_query::Query::Field(name, query) => {
+            let field =
+                Field::from_name(name).ok_or_else(|| anyhow::anyhow!("unknown field: {name}"))?;
+            eval_term(query, field, index)
         }
-        anubistats_query::Query::Or(lhs, rhs) => {
-            let lhs = eval_query(lhs, find_postings_list)?;
-            let rhs = eval_query(rhs, find_postings_list)?;
-            Ok(lhs | rhs)
+        anubistats_query::Query::Exact(_) => {
+            unreachable!("exact terms are rewritten by analyze_query")
+        }
+        anubistats_query::Query::And(_, _)
+        | anubistats_query::Query::Or(_, _)
+        | anubistats_query::Query::Not(_) => {
+            unreachable!("normalized terms do not contain boolean operators")
         }
     }
 }
 
+/// Estimates the number of documents matching a term of a normalized query in `field`.
+///
+/// Words are estimated by their document frequencies, and the terms expanding to many words or
+/// reading the stored fields are pessimistically estimated to match every document.
+fn estimate_term<I: InvertedIndex>(query: &Query, field: Field, index: &I) -> anyhow::Result<u64> {
+    match query {
+        Query::Word(word) => index.document_frequency(field, word),
+        // A phrase or a proximity can only match the documents containing its rarest word.
+        Query::Phrase(words) => words
+            .iter()
+            .flatten()
+            .map(|word| index.document_frequency(field, word))
+            .try_fold(u64::MAX, |min, frequency| Ok(min.min(frequency?))),
+        Query::Near(lhs, rhs, _) => Ok(index
+            .document_frequency(field, lhs)?
+            .min(index.document_frequency(field, rhs)?)),
+        Query::Field(name, query) => match Field::from_name(name) {
+            Some(field) => estimate_term(query, field, index),
+            None => Ok(index.universe().len()),
+        },
+        _ => Ok(index.universe().len()),
+    }
+}
+
+/// Estimates the number of documents matching `query`, which is the cost of evaluating it.
+fn estimate_query<I: InvertedIndex>(query: &NormalizedQuery, index: &I) -> anyhow::Result<u64> {
+    match query {
+        NormalizedQuery::Term(query) => estimate_term(query, Field::DEFAULT, index),
+        NormalizedQuery::And(queries) => queries
+            .iter()
+            .filter(|query| !matches!(query, NormalizedQuery::Not(_)))
+            .try_fold(index.universe().len(), |min, query| {
+                Ok(min.min(estimate_query(query, index)?))
+            }),
+        NormalizedQuery::Or(queries) => queries.iter().try_fold(0u64, |sum, query| {
+            Ok(sum.saturating_add(estimate_query(query, index)?))
+        }),
+        NormalizedQuery::Not(_) => Ok(index.universe().len()),
+    }
+}
+
+/// Splits the operands of a conjunction into the positive ones sorted by their estimated costs and
+/// the negated ones, which are evaluated as set differences without materializing the complements.
+///
+/// Starting from the rarest operand keeps the intermediate results small.
+fn plan_conjunction<'a, I: InvertedIndex>(
+    queries: &'a [NormalizedQuery],
+    index: &I,
+) -> anyhow::Result<(Vec<&'a NormalizedQuery>, Vec<&'a NormalizedQuery>)> {
+    let (negated, queries): (Vec<_>, Vec<_>) = queries
+        .iter()
+        .partition(|query| matches!(query, NormalizedQuery::Not(_)));
+    let mut queries = queries
+        .into_iter()
+        .map(|query| Ok((estimate_query(query, index)?, query)))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    queries.sort_by_key(|&(cost, _)| cost);
+    Ok((
+        queries.into_iter().map(|(_, query)| query).collect(),
+        negated,
+    ))
+}
+
+/// Evaluates `query` against `index`.
+fn eval_query<I: InvertedIndex>(
+    query: &NormalizedQuery,
+    index: &I,
+) -> anyhow::Result<RoaringBitmap> {
+    Ok(eval_node(query, index, false)?.0)
+}
+
+/// Evaluates `query` along with the explanation of how each node was evaluated.
+fn explain_query<'a, I: InvertedIndex>(
+    query: &'a NormalizedQuery,
+    index: &I,
+) -> anyhow::Result<(RoaringBitmap, Explanation<'a>)> {
+    let (result, explanation) = eval_node(query, index, true)?;
+    Ok((result, explanation.expect("the explanation is recorded")))
+}
+
+/// Evaluates `query` against `index`, returning its explanation as well if `explain` is true.
+///
+/// `eval_query` and `explain_query` share this evaluation so that what `:explain` shows is how the
+/// queries are actually evaluated.
+fn eval_node<'a, I: InvertedIndex>(
+    query: &'a NormalizedQuery,
+    index: &I,
+    explain: bool,
+) -> anyhow::Result<(RoaringBitmap, Option<Explanation<'a>>)> {
+    let mut recorder = Recorder::new(query, explain);
+
+    let result = match query {
+        NormalizedQuery::Term(term) => recorder.measure(
+            |explanation| &mut explanation.lookup_time,
+            || eval_term(term, Field::DEFAULT, index),
+        )?,
+        NormalizedQuery::And(queries) => {
+            let (queries, negated) = recorder.measure(
+                |explanation| &mut explanation.estimation_time,
+                || plan_conjunction(queries, index),
+            )?;
+
+            // The operands are intersected one by one instead of by `MultiOps::intersection`, which
+            // needs all of them materialized, so that the rest are skipped once the result is empty.
+            let mut result: Option<RoaringBitmap> = None;
+            for query in queries {
+                if matches!(&result, Some(result) if result.is_empty()) {
+                    recorder.skip(query);
+                    continue;
+                }
+                let (documents, child) = eval_node(query, index, explain)?;
+                recorder.push(child);
+                result = Some(match result {
+                    Some(mut result) => {
+                        recorder.measure(
+                            |explanation| &mut explanation.operation_time,
+                            || result &= documents,
+                        );
+                        result
+                    }
+                    None => documents,
+                });
+            }
+            let mut result = match result {
+                Some(result) => result,
+                None => recorder.measure(
+                    |explanation| &mut explanation.operation_time,
+                    || index.universe().clone(),
+                ),
+            };
+
+            // The negated operands are subtracted, so their complements are not materialized.
+            let mut subtrahends = vec![];
+            for query in negated {
+                let NormalizedQuery::Not(inner) = query else {
+                    unreachable!()
+                };
+                if result.is_empty() {
+                    recorder.skip(query);
+                    continue;
+                }
+                let (documents, child) = eval_node(inner, index, explain)?;
+                subtrahends.push(documents);
+                recorder.push(child.map(|child| Explanation {
+                    children: vec![child],
+                    ..Explanation::skipped(query)
+                }));
+            }
+            if !subtrahends.is_empty() {
+                recorder.measure(
+                    |explanation| &mut explanation.operation_time,
+                    || result -= subtrahends.union(),
+                );
+            }
+            result
+        }
+        NormalizedQuery::Or(queries) => {
+            let mut operands = Vec::with_capacity(queries.len());
+            for query in queries {
+                let (documents, child) = eval_node(query, index, explain)?;
+                operands.push(documents);
+                recorder.push(child);
+            }
+            recorder.measure(
+                |explanation| &mut explanation.operation_time,
+                || operands.union(),
+            )
+        }
+        NormalizedQuery::Not(negated) => {
+            let (documents, child) = eval_node(negated, index, explain)?;
+            recorder.push(child);
+            recorder.measure(
+                |explanation| &mut explanation.operation_time,
+                || index.universe() - documents,
+            )
+        }
+    };
+
+    let explanation = recorder.finish(&result);
+    Ok((result, explanation))
+}
+
+/// The evaluation of a node of a normalized query, shown by the `:explain` command.
+struct Explanation<'a> {
+    query: &'a NormalizedQuery,
+    /// The number of documents matching the node, or `None` if the node was not materialized
+    /// because its conjunction was already empty or it was subtracted from its conjunction.
+    cardinality: Option<u64>,
+    /// The seconds spent estimating the costs of the operands of a conjunction to order them.
+    estimation_time: f64,
+    /// The seconds spent reading the postings lists for the node, excluding its children.
+    lookup_time: f64,
+    /// The seconds spent combining the bitmaps for the node, excluding its children.
+    operation_time: f64,
+    children: Vec<Explanation<'a>>,
+}
+
+impl<'a> Explanation<'a> {
+    fn skipped(query: &'a NormalizedQuery) -> Self {
+        Self {
+            query,
+            cardinality: None,
+            estimation_time: 0.0,
+            lookup_time: 0.0,
+            operation_time: 0.0,
+            children: vec![],
+        }
+    }
+
+    fn write(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        write!(f, "{}{}: ", "  ".repeat(depth), self.query)?;
+        match self.cardinality {
+            Some(cardinality) => write!(f, "{cardinality} documents")?,
+            None => write!(f, "not materialized")?,
+        }
+        write!(f, " (")?;
+        if matches!(self.query, NormalizedQuery::And(_)) {
+            write!(f, "estimation {:.8} ms, ", self.estimation_time * 1000.0)?;
+        }
+        writeln!(
+            f,
+            "lookup {:.8} ms, bitmap operations {:.8} ms)",
+            self.lookup_time * 1000.0,
+            self.operation_time * 1000.0
+        )?;
+        for child in self.children.iter() {
+            child.write(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Explanation<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+/// Records the explanation of a node while `eval_node` evaluates it, or nothing if the node is not
+/// explained so that the plain evaluation does not measure the timings.
+struct Recorder<'a>(Option<Explanation<'a>>);
+
+impl<'a> Recorder<'a> {
+    fn new(query: &'a NormalizedQuery, explain: bool) -> Self {
+        Self(explain.then(|| Explanation::skipped(query)))
+    }
+
+    /// Calls `f`, adding the seconds it takes to the timing of the explanation that `timing`
+    /// selects.
+    fn measure<F, R>(
+        &mut self,
+        timing: for<'e> fn(&'e mut Explanation<'a>) -> &'e mut f64,
+        f: F,
+    ) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        match &mut self.0 {
+            Some(explanation) => {
+                let (time, result) = measure_time(f);
+                *timing(explanation) += time;
+                result
+            }
+            None => f(),
+        }
+    }
+
+    /// Adds the explanation of a child node.
+    fn push(&mut self, child: Option<Explanation<'a>>) {
+        if let (Some(explanation), Some(child)) = (&mut self.0, child) {
+            explanation.children.push(child);
+        }
+    }
+
+    /// Adds a child node that was not evaluated.
+    fn skip(&mut self, query: &'a NormalizedQuery) {
+        if let Some(explanation) = &mut self.0 {
+            explanation.children.push(Explanation::skipped(query));
+        }
+    }
+
+    fn finish(self, result: &RoaringBitmap) -> Option<Explanation<'a>> {
+        self.0.map(|explanation| Explanation {
+            cardinality: Some(result.len()),
+            ..explanation
+        })
+    }
+}
+
 struct Document {
     roaring_id: u32,
     doc_id: u64,
@@ -289,6 +1483,67 @@ fn group_scores_by_date(roaring_ids_filter: RoaringBitmap) -> anyhow::Result<Sco
     })
 }
 
+/// Counts the matched documents from each domain, in the descending order of the counts.
+///
+/// The domains are stored in a dictionary-encoded column, so the documents are counted by the keys
+/// of the dictionary in each batch before the domains are looked up.
+fn count_documents_by_domain(
+    roaring_ids_filter: RoaringBitmap,
+) -> anyhow::Result<Vec<(String, u64)>> {
+    let file = File::open("stored_fields.parquet")?;
+    let builder = ParquetRecordBatchReaderBuilder::try_new(file)?;
+    let column_index = |name: &str| {
+        builder
+            .parquet_schema()
+            .columns()
+            .iter()
+            .position(|c| c.name() == name)
+    };
+    let id_column = column_index("id").unwrap();
+    let domain_column = column_index("domain")
+        .ok_or_else(|| anyhow::anyhow!("the stored fields have no domains; rebuild the index"))?;
+
+    // Construct a reader that only reads the domains of the rows that have matching roaring IDs.
+    let predicate = ArrowPredicateFn::new(
+        ProjectionMask::leaves(builder.parquet_schema(), std::iter::once(id_column)),
+        move |batch| {
+            let roaring_ids: &UInt32Array = batch.column(0).as_primitive();
+            Ok(BooleanArray::from_unary(roaring_ids, |roaring_id| {
+                roaring_ids_filter.contains(roaring_id)
+            }))
+        },
+    );
+    let projection = ProjectionMask::leaves(builder.parquet_schema(), [id_column, domain_column]);
+    let row_filter = RowFilter::new(vec![Box::new(predicate)]);
+    let reader = builder
+        .with_projection(projection)
+        .with_row_filter(row_filter)
+        .build()?;
+
+    let mut counts: HashMap<String, u64> = HashMap::new();
+    for batch in reader {
+        let batch = batch?;
+        let domains = batch["domain"].as_dictionary::<Int32Type>();
+
+        let mut key_counts = vec![0; domains.values().len()];
+        for key in domains.keys().iter().flatten() {
+            key_counts[usize::try_from(key)?] += 1;
+        }
+        let values: &StringArray = domains.values().as_string();
+        for (key, count) in key_counts.into_iter().enumerate() {
+            if count > 0 {
+                *counts.entry(values.value(key).to_string()).or_default() += count;
+            }
+        }
+    }
+
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by(|(lhs_domain, lhs), (rhs_domain, rhs)| {
+        rhs.cmp(lhs).then_with(|| lhs_domain.cmp(rhs_domain))
+    });
+    Ok(counts)
+}
+
 fn measure_time<F, R>(f: F) -> (f64, R)
 where
     F: FnOnce() -> R,
@@ -302,28 +1557,102 @@ where
 }
 
 fn main() -> anyhow::Result<()> {
+    let mut max_expansions = DEFAULT_MAX_EXPANSIONS;
+    let mut parse_options = ParseOptions::default();
+    let mut analyzer = Analyzer::default();
+    for arg in std::env::args().skip(1) {
+        if let Some(value) = arg.strip_prefix("--analyzer=") {
+            analyzer = value.parse()?;
+        } else if let Some(value) = arg.strip_prefix("--max-expansions=") {
+            max_expansions = value.parse()?;
+        } else if let Some(value) = arg.strip_prefix("--default-operator=") {
+            parse_options.default_operator = match value {
+                "and" => DefaultOperator::And,
+                "or" => DefaultOperator::Or,
+                _ => anyhow::bail!("unknown default operator: {value}"),
+            };
+        } else if let Some(value) = arg.strip_prefix("--precedence=") {
+            parse_options.precedence = match value {
+                "legacy" => Precedence::Legacy,
+                "standard" => Precedence::Standard,
+                _ => anyhow::bail!("unknown precedence: {value}"),
+            };
+        } else {
+            anyhow::bail!("unknown argument: {arg}");
+        }
+    }
+
+    let index = ParquetIndex::open(analyzer, max_expansions)?;
+
     // REPL for querying the postings lists.
-    println!("Enter a query:");
+    println!("Enter a query ({parse_options}), or `:explain <query>` to show its evaluation:");
     let stdin = std::io::stdin().lock();
     for line in stdin.lines() {
         let line = line?;
         let query = line.trim();
-        let query = match anubistats_query::parse(query) {
+        let (explain, query) = match query.strip_prefix(":explain") {
+            Some(rest) if rest.is_empty() || rest.starts_with(char::is_whitespace) => {
+                (true, rest.trim_start())
+            }
+            _ => (false, query),
+        };
+        let query = match anubistats_query::parse_with_options(query, parse_options) {
             Ok(query) => query,
-            Err(_) => {
-                eprintln!("parse error");
+            Err(error) => {
+                // Point at the error under the query.
+                let column = query[..error.offset].chars().count();
+                eprintln!("parse error:");
+                eprintln!("  {query}");
+                eprintln!("  {}^ {error}", " ".repeat(column));
+                continue;
+            }
+        };
+        let mut ignored = vec![];
+        let analyzed = match analyze_query(&query, Field::DEFAULT, index.analyzer(), &mut ignored) {
+            Ok(Some(analyzed)) => analyzed.normalize(),
+            Ok(None) => {
+                eprintln!(
+                    "analysis error: {query} has no terms to search for, since all of its words are \
+                     stopwords"
+                );
+                continue;
+            }
+            Err(error) => {
+                eprintln!("analysis error: {error}");
                 continue;
             }
         };
+        if !ignored.is_empty() {
+            eprintln!(
+                "Ignored {} since they are stopwords or have no terms to search for",
+                ignored.join(", ")
+            );
+        }
 
-        let (eval_query_time, postings_lists) =
-            measure_time(|| eval_query(&query, &find_postings_list_parquet));
-        let postings_lists = postings_lists?;
+        if explain {
+            let (explain_query_time, explanation) =
+                measure_time(|| explain_query(&analyzed, &index));
+            match explanation {
+                Ok((_, explanation)) => print!("{explanation}"),
+                Err(error) => eprintln!("evaluation error: {error}"),
+            }
+            eprintln!("Evaluated query in {:.8} ms", explain_query_time * 1000.0);
+            continue;
+        }
+
+        let (eval_query_time, postings_lists) = measure_time(|| eval_query(&analyzed, &index));
+        let postings_lists = match postings_lists {
+            Ok(postings_lists) => postings_lists,
+            Err(error) => {
+                eprintln!("evaluation error: {error}");
+                continue;
+            }
+        };
 
         eprintln!("Evaluated query in {:.8} ms", eval_query_time * 1000.0);
 
         println!(
-            "{} documents match the query '{:?}'",
+            "{} documents match the query '{}'",
             postings_lists.len(),
             query
         );
@@ -336,6 +1665,17 @@ fn main() -> anyhow::Result<()> {
             );
         }
 
+        println!("Which domains are the matched documents from?");
+
+        match count_documents_by_domain(postings_lists.clone()) {
+            Ok(counts) => {
+                for (domain, count) in counts.iter().take(5) {
+                    println!("{domain}: {count} documents");
+                }
+            }
+            Err(error) => eprintln!("aggregation error: {error}"),
+        }
+
         println!("How many scores the matched documents have on each date?");
 
         let group_by_result = group_scores_by_date(postings_lists)?;
@@ -351,3 +1691,405 @@ fn main() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, collections::BTreeMap};
+
+    use anubistats::postings::PostingsListBuilder;
+
+    use super::*;
+
+    /// An index of titles kept in memory, which logs how it is read.
+    struct MemoryIndex {
+        universe: RoaringBitmap,
+        analyzer: Analyzer,
+        postings_lists: BTreeMap<(Field, String), PostingsList>,
+        /// The reads of the index in order, like `postings_list(rust)`.
+        reads: RefCell<Vec<String>>,
+    }
+
+    impl MemoryIndex {
+        /// Indexes `titles` as the documents numbered in their order.
+        fn new(titles: &[&str]) -> Self {
+            Self::with_analyzer(titles, Analyzer::default())
+        }
+
+        fn with_analyzer(titles: &[&str], analyzer: Analyzer) -> Self {
+            let mut builders: BTreeMap<(Field, String), PostingsListBuilder> = BTreeMap::new();
+            for (document, title) in (0..).zip(titles) {
+                for (position, word) in analyzer.analyze_for_index(Field::Title, title) {
+                    builders
+                        .entry((Field::Title, word))
+                        .or_default()
+                        .push(document, position.try_into().unwrap());
+                }
+            }
+
+            Self {
+                universe: (0..).take(titles.len()).collect(),
+                analyzer,
+                postings_lists: builders
+                    .into_iter()
+                    .map(|(key, builder)| (key, builder.finish()))
+                    .collect(),
+                reads: RefCell::default(),
+            }
+        }
+
+        fn read(&self, field: Field, word: &str, read: &str) -> PostingsList {
+            self.reads.borrow_mut().push(format!("{read}({word})"));
+            self.postings_lists
+                .get(&(field, word.to_string()))
+                .cloned()
+                .unwrap_or_default()
+        }
+
+        /// Returns the reads of the index since the last call.
+        fn take_reads(&self) -> Vec<String> {
+            self.reads.take()
+        }
+    }
+
+    impl InvertedIndex for MemoryIndex {
+        fn universe(&self) -> &RoaringBitmap {
+            self.reads.borrow_mut().push("universe".to_string());
+            &self.universe
+        }
+
+        fn analyzer(&self) -> &Analyzer {
+            &self.analyzer
+        }
+
+        fn postings_list(&self, field: Field, word: &str) -> anyhow::Result<RoaringBitmap> {
+            Ok(self.read(field, word, "postings_list").documents)
+        }
+
+        fn document_frequency(&self, field: Field, word: &str) -> anyhow::Result<u64> {
+            Ok(self.read(field, word, "document_frequency").documents.len())
+        }
+
+        fn positional_postings_list(
+            &self,
+            field: Field,
+            word: &str,
+        ) -> anyhow::Result<PostingsList> {
+            Ok(self.read(field, word, "positional_postings_list"))
+        }
+
+        fn expand_prefix<F>(
+            &self,
+            field: Field,
+            prefix: &str,
+            mut filter: F,
+        ) -> anyhow::Result<Vec<RoaringBitmap>>
+        where
+            F: FnMut(&str) -> bool,
+        {
+            self.reads
+                .borrow_mut()
+                .push(format!("expand_prefix({prefix})"));
+            Ok(self
+                .postings_lists
+                .range((field, prefix.to_string())..)
+                .take_while(|((f, word), _)| *f == field && word.starts_with(prefix))
+                .filter(|((_, word), _)| filter(word))
+                .map(|(_, postings_list)| postings_list.documents.clone())
+                .collect())
+        }
+
+        fn expand_fuzzy(
+            &self,
+            field: Field,
+            word: &str,
+            max_distance: u32,
+        ) -> anyhow::Result<Vec<RoaringBitmap>> {
+            let mut matcher = FuzzyMatcher::new(word, max_distance);
+            self.expand_prefix(field, "", |candidate| matcher.matches(candidate))
+        }
+
+        fn stored_field_range(&self, _: &StoredFieldRange) -> anyhow::Result<RoaringBitmap> {
+            anyhow::bail!("the stored fields are not kept in memory")
+        }
+    }
+
+    fn documents(bitmap: RoaringBitmap) -> Vec<u32> {
+        bitmap.into_iter().collect()
+    }
+
+    fn phrase(words: &[&str], index: &MemoryIndex) -> Vec<u32> {
+        let words: Vec<_> = words.iter().map(|word| Some(word.to_string())).collect();
+        documents(eval_phrase(Field::Title, &words, index).unwrap())
+    }
+
+    #[test]
+    fn test_eval_phrase() {
+        let index = MemoryIndex::new(&[
+            "memory safe rust code",
+            "rust memory safe code",
+            "safe memory",
+            "memory leaks make code unsafe",
+        ]);
+        assert_eq!(phrase(&["memory", "safe"], &index), vec![0, 1]);
+        assert_eq!(phrase(&["safe", "memory"], &index), vec![2]);
+        assert_eq!(phrase(&["memory", "safe", "rust"], &index), vec![0]);
+        assert_eq!(phrase(&["memory", "code"], &index), Vec::<u32>::new());
+        assert_eq!(phrase(&["memory", "python"], &index), Vec::<u32>::new());
+        assert_eq!(phrase(&["code"], &index), vec![0, 1, 3]);
+    }
+
+    #[test]
+    fn test_eval_near() {
+        let index = MemoryIndex::new(&[
+            "memory safe rust code",
+            "rust memory safe code",
+            "safe memory",
+            "memory leaks make code unsafe",
+        ]);
+        let near = |lhs, rhs, distance| {
+            documents(eval_near(Field::Title, lhs, rhs, distance, &index).unwrap())
+        };
+        assert_eq!(near("memory", "code", 2), vec![1]);
+        assert_eq!(near("code", "memory", 2), vec![1]);
+        assert_eq!(near("memory", "code", 3), vec![0, 1, 3]);
+        assert_eq!(near("safe", "memory", 1), vec![0, 1, 2]);
+        assert_eq!(near("memory", "python", 10), Vec::<u32>::new());
+    }
+
+    fn normalize(input: &str, index: &MemoryIndex) -> NormalizedQuery {
+        let query = anubistats_query::parse(input).unwrap();
+        analyze_query(&query, Field::DEFAULT, index.analyzer(), &mut vec![])
+            .unwrap()
+            .unwrap()
+            .normalize()
+    }
+
+    fn reads(reads: &[&str]) -> Vec<String> {
+        reads.iter().map(|read| read.to_string()).collect()
+    }
+
+    const FRAMEWORKS: [&str; 5] = [
+        "rust compiler",
+        "rust web framework",
+        "rust async runtime",
+        "go web framework",
+        "rust gui",
+    ];
+
+    #[test]
+    fn test_plan_conjunction() {
+        let index = MemoryIndex::new(&FRAMEWORKS);
+        let query = normalize("rust web async -go", &index);
+        let NormalizedQuery::And(queries) = &query else {
+            panic!("{query} is not a conjunction");
+        };
+        let (queries, negated) = plan_conjunction(queries, &index).unwrap();
+        let queries: Vec<_> = queries.iter().map(|query| query.to_string()).collect();
+        let negated: Vec<_> = negated.iter().map(|query| query.to_string()).collect();
+        assert_eq!(queries, vec!["async", "web", "rust"]);
+        assert_eq!(negated, vec!["-go"]);
+        // The costs are estimated from the document frequencies without reading the postings lists,
+        // and the negated operands are not estimated.
+        assert_eq!(
+            index.take_reads(),
+            reads(&[
+                "document_frequency(async)",
+                "document_frequency(rust)",
+                "document_frequency(web)"
+            ])
+        );
+    }
+
+    #[test]
+    fn test_eval_conjunction() {
+        let index = MemoryIndex::new(&FRAMEWORKS);
+
+        // The rest of the operands are not read once the result is empty.
+        let query = normalize("rust web compiler -go", &index);
+        assert!(eval_query(&query, &index).unwrap().is_empty());
+        assert_eq!(
+            index.take_reads(),
+            reads(&[
+                "document_frequency(compiler)",
+                "document_frequency(rust)",
+                "document_frequency(web)",
+                "postings_list(compiler)",
+                "postings_list(web)"
+            ])
+        );
+
+        // The negated operands are subtracted without building their complements from the universe.
+        let query = normalize("rust -web -go", &index);
+        assert_eq!(
+            documents(eval_query(&query, &index).unwrap()),
+            vec![0, 2, 4]
+        );
+        assert_eq!(
+            index.take_reads(),
+            reads(&[
+                "document_frequency(rust)",
+                "postings_list(rust)",
+                "postings_list(go)",
+                "postings_list(web)"
+            ])
+        );
+
+        let query = normalize("framework -go", &index);
+        assert_eq!(documents(eval_query(&query, &index).unwrap()), vec![1]);
+        let query = normalize("-rust", &index);
+        assert_eq!(documents(eval_query(&query, &index).unwrap()), vec![3]);
+    }
+
+    /// Returns the nodes of `explanation` with their cardinalities, indented by their depths.
+    fn outline(explanation: &Explanation<'_>, depth: usize) -> Vec<String> {
+        let cardinality = match explanation.cardinality {
+            Some(cardinality) => cardinality.to_string(),
+            None => "-".to_string(),
+        };
+        std::iter::once(format!(
+            "{}{}: {cardinality}",
+            "  ".repeat(depth),
+            explanation.query
+        ))
+        .chain(
+            explanation
+                .children
+                .iter()
+                .flat_map(|child| outline(child, depth + 1)),
+        )
+        .collect()
+    }
+
+    #[test]
+    fn test_explain_query() {
+        let index = MemoryIndex::new(&FRAMEWORKS);
+        for (input, expected) in [
+            (
+                "rust web compiler -go",
+                vec![
+                    "compiler AND rust AND web AND -go: 0",
+                    "  compiler: 1",
+                    "  web: 2",
+                    "  rust: -",
+                    "  -go: -",
+                ],
+            ),
+            (
+                "rust -web (gui OR async)",
+                vec![
+                    "rust AND -web AND (async OR gui): 2",
+                    "  async OR gui: 2",
+                    "    async: 1",
+                    "    gui: 1",
+                    "  rust: 4",
+                    "  -web: -",
+                    "    web: 2",
+                ],
+            ),
+            ("-rust", vec!["-rust: 1", "  rust: 4"]),
+        ] {
+            let query = normalize(input, &index);
+            let (result, explanation) = explain_query(&query, &index).unwrap();
+            assert_eq!(outline(&explanation, 0), expected, "{input}");
+            let explain_reads = index.take_reads();
+
+            // The explanation shows the evaluation done without it.
+            assert_eq!(eval_query(&query, &index).unwrap(), result, "{input}");
+            assert_eq!(index.take_reads(), explain_reads, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_analyze_query() {
+        for (input, analyzed, ignored) in [
+            (
+                "the rust AND (of OR web) AND -an",
+                Some("rust AND web"),
+                vec!["the", "of", "an"],
+            ),
+            ("\"state of the art\"", Some("\"state ? ? art\""), vec![]),
+            ("\"state ? of art\"", Some("\"state ? ? art\""), vec![]),
+            (
+                "\"the state\" OR \"of the\"",
+                Some("state"),
+                vec!["\"of the\""],
+            ),
+            ("title:the rust", Some("rust"), vec!["the"]),
+            ("the NEAR/2 state", Some("state"), vec!["the"]),
+            ("State NEAR/2 the", Some("state"), vec!["the"]),
+            ("Rust NEAR/2 State", Some("rust NEAR/2 state"), vec![]),
+            ("the NEAR/2 of", None, vec!["the", "of"]),
+        ] {
+            let mut actual_ignored = vec![];
+            let query = analyze_query(
+                &anubistats_query::parse(input).unwrap(),
+                Field::DEFAULT,
+                &Analyzer::default(),
+                &mut actual_ignored,
+            )
+            .unwrap();
+            assert_eq!(
+                query.as_ref().map(Query::to_string).as_deref(),
+                analyzed,
+                "{input}"
+            );
+            assert_eq!(actual_ignored, ignored, "{input}");
+            // The analyzed queries are shown to the users, so they must parse back.
+            if let Some(query) = query {
+                assert_eq!(anubistats_query::parse(&query.to_string()), Ok(query));
+            }
+        }
+
+        let error = analyze_query(
+            &anubistats_query::parse("state-of-the-art NEAR/2 rust").unwrap(),
+            Field::DEFAULT,
+            &Analyzer::default(),
+            &mut vec![],
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "the operands of NEAR must be single terms, but state-of-the-art is not"
+        );
+    }
+
+    #[test]
+    fn test_eval_phrase_with_stopwords() {
+        let index = MemoryIndex::new(&[
+            "the state of the art",
+            "state art",
+            "state of modern art",
+            "art of the state",
+        ]);
+        let search = |input| documents(eval_query(&normalize(input, &index), &index).unwrap());
+        assert_eq!(search("\"state of the art\""), vec![0, 2]);
+        assert_eq!(search("\"state ? ? art\""), vec![0, 2]);
+        assert_eq!(search("\"state art\""), vec![1]);
+        assert_eq!(search("state NEAR/3 art"), vec![0, 1, 2, 3]);
+        assert_eq!(search("state NEAR/2 art"), vec![1]);
+    }
+
+    #[test]
+    fn test_eval_cjk() {
+        let index = MemoryIndex::with_analyzer(
+            &[
+                "東京都庁",
+                "京都の寺",
+                "東 rust",
+                "rust 東京都",
+                "東京都 rust",
+            ],
+            "cjk+fold".parse().unwrap(),
+        );
+        let search = |input| documents(eval_query(&normalize(input, &index), &index).unwrap());
+        assert_eq!(search("東"), vec![0, 2, 3, 4]);
+        assert_eq!(search("都"), vec![0, 1, 3, 4]);
+        assert_eq!(search("東京都"), vec![0, 3, 4]);
+        assert_eq!(search("京都"), vec![0, 1, 3, 4]);
+        assert_eq!(search("\"東京都 rust\""), vec![4]);
+        assert_eq!(search("\"rust 東京\""), vec![3]);
+        assert_eq!(search("\"東 rust\""), vec![2]);
+        assert_eq!(search("\"都 rust\""), vec![4]);
+        assert_eq!(search("東 NEAR/1 rust"), vec![2, 3]);
+    }
+}
@@ -0,0 +1,240 @@
+//! The fields of the Hacker News entries that are indexed for searching.
+
+use std::{borrow::Cow, net::IpAddr};
+
+use crate::{html::html_to_text, Record};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Field {
+    Title,
+    By,
+    Url,
+    Text,
+    Author,
+    /// The titles with the words kept unstemmed, which is indexed only if the analyzer stems.
+    ExactTitle,
+    /// The story texts with the words kept unstemmed, which is indexed only if the analyzer stems.
+    ExactText,
+    /// The registrable domains of the URLs like `nytimes.com`, which are matched as a whole.
+    Domain,
+}
+
+impl Field {
+    pub const ALL: [Field; 8] = [
+        Field::Title,
+        Field::By,
+        Field::Url,
+        Field::Text,
+        Field::Author,
+        Field::ExactTitle,
+        Field::ExactText,
+        Field::Domain,
+    ];
+
+    /// The field that terms without a field name are searched in.
+    pub const DEFAULT: Field = Field::Title;
+
+    /// Returns the name of the field used in queries like `by:pg`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Field::Title => "title",
+            Field::By => "by",
+            Field::Url => "url",
+            Field::Text => "text",
+            Field::Author => "author",
+            Field::ExactTitle => "exact_title",
+            Field::ExactText => "exact_text",
+            Field::Domain => "domain",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Field> {
+        Field::ALL.into_iter().find(|field| field.name() == name)
+    }
+
+    /// Returns the path of the file storing the postings lists for this field.
+    ///
+    /// The postings lists of the titles keep the file name from the time only titles were indexed.
+    pub fn postings_lists_path(self) -> &'static str {
+        match self {
+            Field::Title => "postings_lists.parquet",
+            Field::By => "postings_lists.by.parquet",
+            Field::Url => "postings_lists.url.parquet",
+            Field::Text => "postings_lists.text.parquet",
+            Field::Author => "postings_lists.author.parquet",
+            Field::ExactTitle => "postings_lists.exact_title.parquet",
+            Field::ExactText => "postings_lists.exact_text.parquet",
+            Field::Domain => "postings_lists.domain.parquet",
+        }
+    }
+
+    /// Returns true if the field is natural language text, like the titles.
+    pub fn is_text(self) -> bool {
+        matches!(
+            self,
+            Field::Title | Field::Text | Field::ExactTitle | Field::ExactText
+        )
+    }
+
+    /// Returns the field keeping the words of this field unstemmed.
+    pub fn exact(self) -> Field {
+        match self {
+            Field::Title => Field::ExactTitle,
+            Field::Text => Field::ExactText,
+            field => field,
+        }
+    }
+
+    /// Returns the field that this unstemmed field is a copy of.
+    pub fn base(self) -> Field {
+        match self {
+            Field::ExactTitle => Field::Title,
+            Field::ExactText => Field::Text,
+            field => field,
+        }
+    }
+
+    pub fn is_exact(self) -> bool {
+        self.base() != self
+    }
+
+    /// Splits the value of this field into the words to index.
+    pub fn tokenize(self, value: &str) -> Vec<&str> {
+        match self {
+            Field::Title | Field::Text | Field::ExactTitle | Field::ExactText => {
+                value.split_whitespace().collect()
+            }
+            // User names and domains are matched as a whole.
+            Field::By | Field::Author | Field::Domain => {
+                let value = value.trim();
+                if value.is_empty() {
+                    vec![]
+                } else {
+                    vec![value]
+                }
+            }
+            Field::Url => url_segments(value),
+        }
+    }
+}
+
+/// Splits a URL into its host and the segments of its path, like `github.com` and `rust-lang` for
+/// `https://github.com/rust-lang`.
+///
+/// The scheme, the user name, the port, the query, and the fragment are dropped, since they rarely
+/// tell what the story is about.
+pub fn url_segments(url: &str) -> Vec<&str> {
+    let (host, path) = split_url(url);
+    std::iter::once(host)
+        .chain(path.split('/'))
+        .filter(|segment| !segment.is_empty())
+        .collect()
+}
+
+/// Splits a URL into its host and its path without the query and the fragment.
+fn split_url(url: &str) -> (&str, &str) {
+    let url = url.trim();
+    let url = match url.split_once("://") {
+        Some((scheme, rest)) if !scheme.contains(['/', '?', '#']) => rest,
+        _ => url,
+    };
+    let url = url.split(['?', '#']).next().unwrap_or_default();
+    let (authority, path) = url.split_once('/').unwrap_or((url, ""));
+
+    let host = authority.rsplit('@').next().unwrap_or_default();
+    let host = match host.strip_prefix('[') {
+        // IPv6 addresses contain colons in the brackets.
+        Some(address) => address.split(']').next().unwrap_or_default(),
+        None => host.split(':').next().unwrap_or_default(),
+    };
+    (host, path)
+}
+
+/// Returns the registrable domain of a URL, which is the public suffix of the host like `com` or
+/// `co.uk` and the label before it, like `nytimes.com` for `https://www.nytimes.com/`.
+///
+/// Returns `None` if the URL has no host with a registrable domain, like the stories without URLs
+/// and the hosts given by IP addresses.
+pub fn registrable_domain(url: &str) -> Option<String> {
+    let host = split_url(url).0.trim_end_matches('.').to_lowercase();
+    if host.parse::<IpAddr>().is_ok() {
+        return None;
+    }
+    psl::domain_str(&host).map(str::to_string)
+}
+
+impl Record {
+    /// Returns the value of `field` to index.
+    ///
+    /// The story texts are HTML, so the tags are stripped and the character references are decoded.
+    pub fn field(&self, field: Field) -> Cow<'_, str> {
+        match field {
+            Field::Title | Field::ExactTitle => Cow::Borrowed(&self.title),
+            Field::By => Cow::Borrowed(&self.by),
+            Field::Url => Cow::Borrowed(&self.url),
+            Field::Text | Field::ExactText => Cow::Owned(html_to_text(&self.text)),
+            Field::Author => Cow::Borrowed(&self.author),
+            Field::Domain => Cow::Owned(self.domain().unwrap_or_default()),
+        }
+    }
+
+    /// Returns the registrable domain of the URL, if any.
+    pub fn domain(&self) -> Option<String> {
+        registrable_domain(&self.url)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        for field in Field::ALL {
+            assert_eq!(Field::from_name(field.name()), Some(field));
+            // The parser only takes the known field names as fields.
+            assert!(
+                anubistats_query::FIELDS.contains(&field.name()),
+                "{}",
+                field.name()
+            );
+        }
+    }
+
+    #[test]
+    fn test_url_segments() {
+        assert_eq!(
+            url_segments("https://github.com/rust-lang/rust/"),
+            vec!["github.com", "rust-lang", "rust"]
+        );
+        assert_eq!(
+            url_segments("http://user@www.example.com:8080/a//b.html?q=1#top"),
+            vec!["www.example.com", "a", "b.html"]
+        );
+        assert_eq!(url_segments("https://[::1]:80/x"), vec!["::1", "x"]);
+        assert_eq!(
+            url_segments("example.com/path"),
+            vec!["example.com", "path"]
+        );
+        assert_eq!(url_segments(" "), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn test_registrable_domain() {
+        for (url, domain) in [
+            (
+                "https://www.nytimes.com/2023/04/15/a.html",
+                Some("nytimes.com"),
+            ),
+            ("https://GitHub.com/rust-lang", Some("github.com")),
+            ("http://news.bbc.co.uk/", Some("bbc.co.uk")),
+            ("https://example.github.io./x", Some("example.github.io")),
+            ("http://127.0.0.1:8080/", None),
+            ("https://[::1]/", None),
+            ("https://co.uk/", None),
+            ("", None),
+        ] {
+            assert_eq!(registrable_domain(url).as_deref(), domain, "{url}");
+        }
+    }
+}
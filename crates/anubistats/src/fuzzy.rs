@@ -0,0 +1,123 @@
+//! Matching of words within a bounded edit distance.
+
+/// Tests whether words are within the maximum Levenshtein distance from the query word.
+///
+/// The matcher keeps the rows of the dynamic programming table for the last tested word, and
+/// reuses the rows for the common prefix with the next word, so that the rows for a prefix shared
+/// by the consecutive words of the sorted dictionary are computed once. The rest of a word is not
+/// compared once its prefix cannot be within the distance anymore, but every word is still tested.
+#[derive(Debug)]
+pub struct FuzzyMatcher {
+    query: Vec<char>,
+    max_distance: u32,
+    /// The characters of the word whose rows are computed.
+    chars: Vec<char>,
+    /// `rows[i][j]` is the edit distance between `chars[..i]` and `query[..j]`.
+    rows: Vec<Vec<u32>>,
+}
+
+impl FuzzyMatcher {
+    pub fn new(query: &str, max_distance: u32) -> Self {
+        let query: Vec<char> = query.chars().collect();
+        let first_row = (0..).take(query.len() + 1).collect();
+        Self {
+            query,
+            max_distance,
+            chars: vec![],
+            rows: vec![first_row],
+        }
+    }
+
+    /// Returns true if `word` is within the maximum distance from the query word.
+    pub fn matches(&mut self, word: &str) -> bool {
+        self.distance(word).is_some()
+    }
+
+    /// Returns the edit distance between `word` and the query word if it is within the maximum
+    /// distance.
+    pub fn distance(&mut self, word: &str) -> Option<u32> {
+        let common_prefix_len = self
+            .chars
+            .iter()
+            .zip(word.chars())
+            .take_while(|(a, b)| *a == b)
+            .count();
+        self.chars.truncate(common_prefix_len);
+        self.rows.truncate(common_prefix_len + 1);
+
+        for c in word.chars().skip(common_prefix_len) {
+            let previous = self.rows.last().unwrap();
+            if previous.iter().min().unwrap() > &self.max_distance {
+                // No word with this prefix can be within the distance.
+                return None;
+            }
+
+            let mut row = Vec::with_capacity(previous.len());
+            row.push(previous[0] + 1);
+            for (j, &q) in self.query.iter().enumerate() {
+                let substitution = previous[j] + u32::from(q != c);
+                let insertion = previous[j + 1] + 1;
+                let deletion = row[j] + 1;
+                row.push(substitution.min(insertion).min(deletion));
+            }
+
+            self.chars.push(c);
+            self.rows.push(row);
+        }
+
+        let distance = self.rows.last().unwrap()[self.query.len()];
+        (distance <= self.max_distance).then_some(distance)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_matcher() {
+        let mut matcher = FuzzyMatcher::new("kubernetes", 1);
+        let words = [
+            "kube",
+            "kubernates",
+            "kubernetes",
+            "kubernetess",
+            "kubernets",
+            "kubrnetes",
+            "kubrnets",
+            "rust",
+        ];
+        let matched: Vec<_> = words
+            .into_iter()
+            .filter(|word| matcher.matches(word))
+            .collect();
+        assert_eq!(
+            matched,
+            vec![
+                "kubernates",
+                "kubernetes",
+                "kubernetess",
+                "kubernets",
+                "kubrnetes"
+            ]
+        );
+
+        let mut matcher = FuzzyMatcher::new("rust", 2);
+        assert!(matcher.matches("bust"));
+        assert!(matcher.matches("rs"));
+        assert!(matcher.matches("rusty"));
+        assert!(!matcher.matches("trustee"));
+        assert!(matcher.matches("rust"));
+        assert!(!matcher.matches("r"));
+    }
+
+    #[test]
+    fn test_distance() {
+        let mut matcher = FuzzyMatcher::new("go", 2);
+        assert_eq!(matcher.distance("go"), Some(0));
+        assert_eq!(matcher.distance("goa"), Some(1));
+        assert_eq!(matcher.distance("rust"), None);
+        assert_eq!(matcher.distance("to"), Some(1));
+        assert_eq!(matcher.distance(""), Some(2));
+    }
+}
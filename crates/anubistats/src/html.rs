@@ -0,0 +1,77 @@
+//! Extraction of the plain text from the HTML of the story texts.
+
+/// The tags separating the text around them, which are replaced with a space instead of being
+/// removed so that the words around them are not joined.
+const BLOCK_TAGS: [&str; 17] = [
+    "p",
+    "br",
+    "pre",
+    "div",
+    "blockquote",
+    "ul",
+    "ol",
+    "li",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "hr",
+    "tr",
+    "td",
+];
+
+/// Returns the text of `html` with the tags stripped and the character references like `&amp;` and
+/// `&#x27;` decoded.
+///
+/// The HTML of the story texts escapes every `<` in the text, so any `<` starts a tag. An unclosed
+/// tag at the end is kept as text.
+pub fn html_to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        let Some(end) = rest[start..].find('>') else {
+            break;
+        };
+        html_escape::decode_html_entities_to_string(&rest[..start], &mut text);
+
+        let tag = &rest[start + 1..start + end];
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default();
+        if BLOCK_TAGS
+            .iter()
+            .any(|block| block.eq_ignore_ascii_case(name))
+        {
+            text.push(' ');
+        }
+        rest = &rest[start + end + 1..];
+    }
+    html_escape::decode_html_entities_to_string(rest, &mut text);
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_html_to_text() {
+        assert_eq!(
+            html_to_text("Hello<p>I&#x27;m <i>really</i> here &amp; there"),
+            "Hello I'm really here & there"
+        );
+        assert_eq!(
+            html_to_text(r#"See <a href="https:&#x2F;&#x2F;example.com" rel="nofollow">this</a>."#),
+            "See this."
+        );
+        assert_eq!(
+            html_to_text("<pre><code>  a &lt;b&gt; c\n</code></pre>d<br/>e"),
+            "   a <b> c\n d e"
+        );
+        assert_eq!(html_to_text("1 &lt; 2 <3"), "1 < 2 <3");
+    }
+}
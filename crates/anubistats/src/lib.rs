@@ -1,4 +1,15 @@
+pub mod analysis;
+pub mod field;
+pub mod fuzzy;
+pub mod html;
+pub mod pattern;
+pub mod postings;
+
 use serde::Deserialize;
+use time::format_description::FormatItem;
+
+/// The format of the dates in the stored fields, which sorts in the chronological order.
+pub const DATE_FORMAT: &[FormatItem<'_>] = time::macros::format_description!("[year][month][day]");
 
 #[derive(Debug, Deserialize)]
 pub struct Record {
@@ -0,0 +1,65 @@
+//! Matching of words against regular expressions.
+
+use regex::Regex;
+use regex_syntax::hir::literal::{ExtractKind, Extractor};
+
+/// Tests whether words match a regular expression in their entirety.
+#[derive(Debug)]
+pub struct RegexMatcher {
+    regex: Regex,
+    prefix: String,
+}
+
+impl RegexMatcher {
+    pub fn new(pattern: &str) -> anyhow::Result<Self> {
+        let regex = Regex::new(&format!("^(?:{pattern})$"))?;
+
+        // Every word matching the pattern starts with the common prefix of the literals the
+        // pattern can start with, which narrows the range of the dictionary to scan.
+        let hir = regex_syntax::Parser::new().parse(pattern)?;
+        let literals = Extractor::new().kind(ExtractKind::Prefix).extract(&hir);
+        let prefix = literals.longest_common_prefix().unwrap_or_default();
+        // The common prefix of byte-oriented literals may end in the middle of a character.
+        let prefix = match std::str::from_utf8(prefix) {
+            Ok(prefix) => prefix,
+            Err(error) => std::str::from_utf8(&prefix[..error.valid_up_to()]).unwrap(),
+        };
+
+        Ok(Self {
+            regex,
+            prefix: prefix.to_string(),
+        })
+    }
+
+    /// Returns the prefix that all the words matching the pattern start with.
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    pub fn matches(&self, word: &str) -> bool {
+        self.regex.is_match(word)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_regex_matcher() {
+        let matcher = RegexMatcher::new("gpt-?[0-9]+").unwrap();
+        assert_eq!(matcher.prefix(), "gpt");
+        assert!(matcher.matches("gpt-4"));
+        assert!(matcher.matches("gpt3"));
+        assert!(!matcher.matches("gpt"));
+        assert!(!matcher.matches("chatgpt-4"));
+
+        let matcher = RegexMatcher::new("rust(acean|c)").unwrap();
+        assert_eq!(matcher.prefix(), "rust");
+        assert!(matcher.matches("rustacean"));
+
+        assert_eq!(RegexMatcher::new(".*db").unwrap().prefix(), "");
+        assert_eq!(RegexMatcher::new("caf(é|è)").unwrap().prefix(), "caf");
+        assert!(RegexMatcher::new("(").is_err());
+    }
+}
@@ -0,0 +1,153 @@
+//! Postings lists with the positions of the word in each document.
+//!
+//! The positions are stored next to the serialized `RoaringBitmap` as a sequence of LEB128 integers:
+//! for each document of the postings list in ascending order, the number of positions followed by
+//! the gaps between the consecutive positions.
+
+use anyhow::Context;
+use roaring::RoaringBitmap;
+
+#[derive(Debug, Default)]
+pub struct PostingsListBuilder {
+    documents: RoaringBitmap,
+    positions: Vec<u8>,
+    current_positions: Vec<u32>,
+}
+
+impl PostingsListBuilder {
+    /// Records that the word appears at `position` in `document`.
+    ///
+    /// Documents must be pushed in ascending order, and positions must be pushed in ascending
+    /// order within a document.
+    pub fn push(&mut self, document: u32, position: u32) {
+        if self.documents.max() != Some(document) {
+            self.flush_current_positions();
+            self.documents.push(document);
+        }
+        self.current_positions.push(position);
+    }
+
+    fn flush_current_positions(&mut self) {
+        if self.current_positions.is_empty() {
+            return;
+        }
+
+        write_varint(&mut self.positions, self.current_positions.len() as u32);
+        let mut previous = 0;
+        for &position in self.current_positions.iter() {
+            write_varint(&mut self.positions, position - previous);
+            previous = position;
+        }
+        self.current_positions.clear();
+    }
+
+    pub fn finish(mut self) -> PostingsList {
+        self.flush_current_positions();
+        PostingsList {
+            documents: self.documents,
+            positions: self.positions,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PostingsList {
+    pub documents: RoaringBitmap,
+    /// The encoded positions of the word in `documents`.
+    pub positions: Vec<u8>,
+}
+
+impl PostingsList {
+    /// Decodes the positions of the word in each document of `selection`, in ascending order of
+    /// the documents.
+    ///
+    /// `selection` must be a subset of the documents of this postings list.
+    pub fn positions_in(&self, selection: &RoaringBitmap) -> anyhow::Result<Vec<Vec<u32>>> {
+        let mut result = Vec::with_capacity(selection.len().try_into()?);
+        let mut bytes = self.positions.as_slice();
+
+        for document in self.documents.iter() {
+            if result.len() as u64 == selection.len() {
+                break;
+            }
+
+            let count = read_varint(&mut bytes)?;
+            if selection.contains(document) {
+                let mut positions = Vec::with_capacity(count.try_into()?);
+                let mut position = 0;
+                for _ in 0..count {
+                    position += read_varint(&mut bytes)?;
+                    positions.push(position);
+                }
+                result.push(positions);
+            } else {
+                for _ in 0..count {
+                    read_varint(&mut bytes)?;
+                }
+            }
+        }
+
+        anyhow::ensure!(
+            result.len() as u64 == selection.len(),
+            "selected documents are not in the postings list"
+        );
+        Ok(result)
+    }
+}
+
+fn write_varint(buffer: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        buffer.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+fn read_varint(bytes: &mut &[u8]) -> anyhow::Result<u32> {
+    let mut value = 0u32;
+    for shift in (0..32).step_by(7) {
+        let (&byte, rest) = bytes.split_first().context("truncated positions")?;
+        *bytes = rest;
+        value |= u32::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    anyhow::bail!("malformed positions")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_positions_roundtrip() {
+        let mut builder = PostingsListBuilder::default();
+        builder.push(1, 0);
+        builder.push(1, 3);
+        builder.push(5, 200);
+        builder.push(7, 1);
+        builder.push(7, 100_000);
+        let postings_list = builder.finish();
+
+        assert_eq!(
+            postings_list.documents.iter().collect::<Vec<_>>(),
+            vec![1, 5, 7]
+        );
+        assert_eq!(
+            postings_list
+                .positions_in(&postings_list.documents)
+                .unwrap(),
+            vec![vec![0, 3], vec![200], vec![1, 100_000]]
+        );
+        assert_eq!(
+            postings_list
+                .positions_in(&[1, 7].into_iter().collect())
+                .unwrap(),
+            vec![vec![0, 3], vec![1, 100_000]]
+        );
+        assert!(postings_list
+            .positions_in(&[2].into_iter().collect())
+            .is_err());
+    }
+}